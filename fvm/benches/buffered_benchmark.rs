@@ -0,0 +1,92 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::Mutex;
+
+use anyhow::Result;
+use cid::multihash::Code;
+use cid::Cid;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fvm::blockstore::{BufferedBlockstore, SyncBufferedBlockstore};
+use fvm_ipld_blockstore::{Blockstore, Buffered, MemoryBlockstore};
+use fvm_ipld_encoding::CborStore;
+
+const THREADS: u64 = 4;
+const BLOCKS_PER_THREAD: u64 = 250;
+
+// A thread-safe base store, so both buffered stores wrap the same thing.
+#[derive(Default)]
+struct LockedBlockstore(Mutex<MemoryBlockstore>);
+
+impl Blockstore for LockedBlockstore {
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        self.0.lock().unwrap().get(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.0.lock().unwrap().put_keyed(k, block)
+    }
+}
+
+/// Writes a batch of leaves and returns their CIDs.
+fn write_leaves<BS: Blockstore>(store: &BS, batch: u64) -> Vec<Cid> {
+    (0..BLOCKS_PER_THREAD)
+        .map(|i| {
+            store
+                .put_cbor(&(batch, black_box(i)), Code::Blake2b256)
+                .unwrap()
+        })
+        .collect()
+}
+
+fn single_threaded(c: &mut Criterion) {
+    c.bench_function("BufferedBlockstore write and flush", |b| {
+        b.iter(|| {
+            let store = BufferedBlockstore::new(LockedBlockstore::default());
+            let leaves: Vec<Cid> = (0..THREADS)
+                .flat_map(|batch| write_leaves(&store, batch))
+                .collect();
+            let root = store.put_cbor(&leaves, Code::Blake2b256).unwrap();
+            store.flush(&root).unwrap();
+        })
+    });
+
+    c.bench_function("SyncBufferedBlockstore write and flush (1 thread)", |b| {
+        b.iter(|| {
+            let store = SyncBufferedBlockstore::new(LockedBlockstore::default());
+            let leaves: Vec<Cid> = (0..THREADS)
+                .flat_map(|batch| write_leaves(&store, batch))
+                .collect();
+            let root = store.put_cbor(&leaves, Code::Blake2b256).unwrap();
+            store.flush(&root).unwrap();
+        })
+    });
+}
+
+fn multi_threaded(c: &mut Criterion) {
+    c.bench_function(
+        &format!("SyncBufferedBlockstore write and flush ({THREADS} threads)"),
+        |b| {
+            b.iter(|| {
+                let store = SyncBufferedBlockstore::new(LockedBlockstore::default());
+                let leaves: Vec<Cid> = std::thread::scope(|s| {
+                    let handles: Vec<_> = (0..THREADS)
+                        .map(|batch| {
+                            let store = &store;
+                            s.spawn(move || write_leaves(store, batch))
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .flat_map(|h| h.join().unwrap())
+                        .collect()
+                });
+                let root = store.put_cbor(&leaves, Code::Blake2b256).unwrap();
+                store.flush(&root).unwrap();
+            })
+        },
+    );
+}
+
+criterion_group!(benches, single_threaded, multi_threaded);
+criterion_main!(benches);
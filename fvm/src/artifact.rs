@@ -0,0 +1,199 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Sinks for the debug artifacts actors store with `debug::store_artifact`.
+//!
+//! Artifacts are only stored when actor debugging is enabled. They're sent to the
+//! [`MachineContext::artifact_sink`] if one is installed, or else written to the directory named by
+//! the `FVM_STORE_ARTIFACT_DIR` environment variable (see [`FilesystemArtifactSink`]).
+//!
+//! [`MachineContext::artifact_sink`]: crate::machine::MachineContext::artifact_sink
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{Context as _, Result};
+use fvm_shared::ActorID;
+
+/// The environment variable naming the directory artifacts are written to when no sink is
+/// installed.
+pub const ENV_ARTIFACT_DIR: &str = "FVM_STORE_ARTIFACT_DIR";
+
+/// Where an artifact was stored from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ArtifactSource {
+    /// The ID of the machine executing the message.
+    pub machine_id: String,
+    /// The actor that sent the top-level message.
+    pub origin: ActorID,
+    /// The nonce of the top-level message.
+    pub nonce: u64,
+    /// The actor that stored the artifact.
+    pub actor: ActorID,
+    /// The invocation the artifact was stored in, counting from 1 at the start of the message
+    /// execution.
+    pub invocation: u64,
+}
+
+/// An artifact stored by an actor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Artifact {
+    pub source: ArtifactSource,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Receives the debug artifacts stored by actors.
+///
+/// Artifact names are validated before they reach the sink: they're non-empty, at most 256 bytes,
+/// don't start with a `.`, and don't contain path separators. Errors returned by the sink are
+/// logged, but aren't reported to the actor.
+///
+/// Any `Fn(&ArtifactSource, &str, &[u8]) -> Result<()>` closure is a sink.
+pub trait ArtifactSink: Send + Sync + 'static {
+    /// Stores an artifact. Artifacts with the same source and name should replace each other.
+    fn store(&self, source: &ArtifactSource, name: &str, data: &[u8]) -> Result<()>;
+}
+
+impl fmt::Debug for dyn ArtifactSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ArtifactSink")
+    }
+}
+
+impl<F> ArtifactSink for F
+where
+    F: Fn(&ArtifactSource, &str, &[u8]) -> Result<()> + Send + Sync + 'static,
+{
+    fn store(&self, source: &ArtifactSource, name: &str, data: &[u8]) -> Result<()> {
+        self(source, name, data)
+    }
+}
+
+/// Writes artifacts to `<dir>/<machine_id>/<origin>/<nonce>/<actor>/<invocation>/<name>`.
+#[derive(Clone, Debug)]
+pub struct FilesystemArtifactSink {
+    dir: PathBuf,
+}
+
+impl FilesystemArtifactSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns a sink writing to the directory named by [`ENV_ARTIFACT_DIR`], if it's set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os(ENV_ARTIFACT_DIR).map(Self::new)
+    }
+}
+
+impl ArtifactSink for FilesystemArtifactSink {
+    fn store(&self, source: &ArtifactSource, name: &str, data: &[u8]) -> Result<()> {
+        let dir = self
+            .dir
+            .join(&source.machine_id)
+            .join(source.origin.to_string())
+            .join(source.nonce.to_string())
+            .join(source.actor.to_string())
+            .join(source.invocation.to_string());
+
+        std::fs::create_dir_all(&dir)
+            .context("failed to make directory to store debug artifacts")?;
+        std::fs::write(dir.join(name), data).context("failed to store debug artifact")?;
+        log::info!("wrote artifact: {} to {:?}", name, dir);
+        Ok(())
+    }
+}
+
+/// Keeps artifacts in memory, in the order they were stored, until they're taken.
+#[derive(Debug, Default)]
+pub struct MemoryArtifactSink {
+    artifacts: Mutex<Vec<Artifact>>,
+}
+
+impl MemoryArtifactSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes and returns all artifacts stored so far.
+    pub fn take(&self) -> Vec<Artifact> {
+        // The list is never left half-modified, so we can ignore lock poisoning.
+        let mut artifacts = self
+            .artifacts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::take(&mut *artifacts)
+    }
+}
+
+impl ArtifactSink for MemoryArtifactSink {
+    fn store(&self, source: &ArtifactSource, name: &str, data: &[u8]) -> Result<()> {
+        let mut artifacts = self
+            .artifacts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        artifacts.retain(|a| !(a.source == *source && a.name == name));
+        artifacts.push(Artifact {
+            source: source.clone(),
+            name: name.to_owned(),
+            data: data.to_vec(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn source(invocation: u64) -> ArtifactSource {
+        ArtifactSource {
+            machine_id: "machine".into(),
+            origin: 100,
+            nonce: 1,
+            actor: 1000,
+            invocation,
+        }
+    }
+
+    #[test]
+    fn memory_sink() {
+        let sink = MemoryArtifactSink::new();
+        sink.store(&source(1), "a", b"1").unwrap();
+        sink.store(&source(1), "b", b"2").unwrap();
+        sink.store(&source(2), "a", b"3").unwrap();
+        // Replaces the first artifact.
+        sink.store(&source(1), "a", b"4").unwrap();
+
+        let artifacts: Vec<_> = sink
+            .take()
+            .into_iter()
+            .map(|a| (a.source.invocation, a.name, a.data))
+            .collect();
+        assert_eq!(
+            artifacts,
+            vec![
+                (1, "b".into(), b"2".to_vec()),
+                (2, "a".into(), b"3".to_vec()),
+                (1, "a".into(), b"4".to_vec()),
+            ]
+        );
+        assert!(sink.take().is_empty());
+    }
+
+    #[test]
+    fn callback_sink() {
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let sink: Arc<dyn ArtifactSink> = Arc::new({
+            let names = names.clone();
+            move |_: &ArtifactSource, name: &str, _: &[u8]| {
+                names.lock().unwrap().push(name.to_owned());
+                Ok(())
+            }
+        });
+        sink.store(&source(1), "a", b"").unwrap();
+        assert_eq!(*names.lock().unwrap(), vec!["a".to_owned()]);
+    }
+}
@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Cursor, Read, Seek};
 
 use anyhow::{anyhow, Result};
@@ -12,6 +12,9 @@ use cid::Cid;
 use fvm_ipld_blockstore::{Blockstore, Buffered};
 use fvm_ipld_encoding::{CBOR, DAG_CBOR};
 use fvm_shared::commcid::{FIL_COMMITMENT_SEALED, FIL_COMMITMENT_UNSEALED};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::gc::Sweep;
 
 /// Wrapper around `Blockstore` to limit and have control over when values are written.
 /// This type is not threadsafe and can only be used in synchronous contexts.
@@ -45,12 +48,24 @@ where
     /// This will recursively traverse the cache and write all data connected by links to this
     /// root Cid. Calling flush will not reset the write buffer.
     fn flush(&self, root: &Cid) -> Result<()> {
-        let mut buffer = Vec::new();
-        let s = self.write.borrow();
-        copy_rec(&s, *root, &mut buffer)?;
+        flush_rec(&self.base, &self.write.borrow(), *root)
+    }
+}
 
-        self.base.put_many_keyed(buffer)?;
+/// Only the write buffer is swept, blocks already in the base store are left alone.
+impl<BS> Sweep for BufferedBlockstore<BS>
+where
+    BS: Blockstore,
+{
+    fn block_cids(&self) -> Vec<Cid> {
+        self.write.borrow().keys().copied().collect()
+    }
 
+    fn remove_blocks(&self, cids: &[Cid]) -> Result<()> {
+        let mut write = self.write.borrow_mut();
+        for cid in cids {
+            write.remove(cid);
+        }
         Ok(())
     }
 }
@@ -112,7 +127,7 @@ fn cbor_read_header_buf<B: Read>(br: &mut B, scratch: &mut [u8]) -> anyhow::Resu
 /// Given a CBOR serialized IPLD buffer, read through all of it and return all the Links.
 /// This function is useful because it is quite a bit more fast than doing this recursively on a
 /// deserialized IPLD object.
-fn scan_for_links<B: Read + Seek, F>(buf: &mut B, mut callback: F) -> Result<()>
+pub(crate) fn scan_for_links<B: Read + Seek, F>(buf: &mut B, mut callback: F) -> Result<()>
 where
     F: FnMut(Cid) -> anyhow::Result<()>,
 {
@@ -163,10 +178,88 @@ where
     Ok(())
 }
 
-/// Copies the IPLD DAG under `root` from the cache to the base store.
+/// Subtrees rooted above this depth are traversed in parallel. Below it, there's too little work
+/// per subtree to be worth spreading across threads.
+const PARALLEL_DEPTH: usize = 3;
+
+/// The maximum total size of the blocks written to the base store in a single batch (unless a
+/// single block is larger).
+const MAX_BATCH_BYTES: usize = 8 << 20;
+
+/// Writes the IPLD DAG under `root` from the cache to the base store, skipping blocks the base
+/// store already has. Blocks are written in bounded batches, children before parents.
+fn flush_rec<BS: Blockstore>(base: &BS, cache: &HashMap<Cid, Vec<u8>>, root: Cid) -> Result<()> {
+    // The base store isn't necessarily thread-safe, so we have to check it serially.
+    let mut missing = Vec::new();
+    for (cid, block) in collect_blocks(cache, root)? {
+        if !base.has(&cid)? {
+            missing.push((cid, block));
+        }
+    }
+    write_blocks(base, missing)
+}
+
+/// Collects the IPLD DAG under `root` from the cache, children before parents, without
+/// duplicates.
+pub(super) fn collect_blocks(
+    cache: &HashMap<Cid, Vec<u8>>,
+    root: Cid,
+) -> Result<Vec<(Cid, &[u8])>> {
+    let mut blocks = Vec::new();
+    copy_rec(cache, root, 0, &mut blocks)?;
+
+    // Blocks reachable through multiple parents are visited multiple times. Keeping the first
+    // copy keeps every block after its children.
+    let mut seen = HashSet::with_capacity(blocks.len());
+    blocks.retain(|(cid, _)| seen.insert(*cid));
+    Ok(blocks)
+}
+
+/// Removes the blocks the base store already has, checking the base store in parallel.
+pub(super) fn retain_missing<BS, D>(base: &BS, blocks: Vec<(Cid, D)>) -> Result<Vec<(Cid, D)>>
+where
+    BS: Blockstore + Sync,
+    D: Send,
+{
+    blocks
+        .into_par_iter()
+        .filter_map(|(cid, block)| match base.has(&cid) {
+            Ok(true) => None,
+            Ok(false) => Some(Ok((cid, block))),
+            Err(e) => Some(Err(e)),
+        })
+        .collect()
+}
+
+/// Writes the blocks to the base store in order, in bounded batches.
+pub(super) fn write_blocks<BS, D>(base: &BS, blocks: Vec<(Cid, D)>) -> Result<()>
+where
+    BS: Blockstore,
+    D: AsRef<[u8]>,
+{
+    let mut batch = Vec::new();
+    let mut batch_bytes = 0;
+    for (cid, block) in blocks {
+        let len = block.as_ref().len();
+        if !batch.is_empty() && batch_bytes + len > MAX_BATCH_BYTES {
+            base.put_many_keyed(batch.drain(..))?;
+            batch_bytes = 0;
+        }
+        batch_bytes += len;
+        batch.push((cid, block));
+    }
+    if !batch.is_empty() {
+        base.put_many_keyed(batch)?;
+    }
+    Ok(())
+}
+
+/// Collects the IPLD DAG under `root` from the cache, in the order it should be written to the
+/// base store.
 fn copy_rec<'a>(
     cache: &'a HashMap<Cid, Vec<u8>>,
     root: Cid,
+    depth: usize,
     buffer: &mut Vec<(Cid, &'a [u8])>,
 ) -> Result<()> {
     const DAG_RAW: u64 = 0x55;
@@ -177,8 +270,8 @@ fn copy_rec<'a>(
     // Differences from lotus (vm.Copy):
     // 1. We assume that if we don't have a block in our buffer, it must already be in the client
     //    and don't check. This should only happen if the client is missing state.
-    // 2. We always write-back new blocks, even if the client already has them. We haven't noticed a
-    //    perf impact.
+    // 2. We only check whether the client already has a block right before writing it, so we
+    //    still traverse (but don't write) subtrees the client already has.
 
     // TODO(M2): Make this not cbor specific.
     // TODO(M2): Allow CBOR (not just DAG_CBOR).
@@ -191,7 +284,7 @@ fn copy_rec<'a>(
         // We shouldn't be creating these at the moment, but lotus' vm.Copy supports them.
         (DAG_CBOR, IDENTITY, _) => {
             return scan_for_links(&mut Cursor::new(root.hash().digest()), |link| {
-                copy_rec(cache, link, buffer)
+                copy_rec(cache, link, depth, buffer)
             })
         }
         // Ignore commitments (not even going to check the hash function.
@@ -218,9 +311,26 @@ fn copy_rec<'a>(
     // In M2, we'll need to copy explicitly.
     if root.codec() == DAG_CBOR {
         // TODO(M2): Make this non-recursive.
-        scan_for_links(&mut Cursor::new(block), |link| {
-            copy_rec(cache, link, buffer)
-        })?;
+        if depth < PARALLEL_DEPTH {
+            let mut links = Vec::new();
+            scan_for_links(&mut Cursor::new(block), |link| {
+                links.push(link);
+                Ok(())
+            })?;
+            let subtrees = links
+                .into_par_iter()
+                .map(|link| {
+                    let mut buffer = Vec::new();
+                    copy_rec(cache, link, depth + 1, &mut buffer)?;
+                    Ok(buffer)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            buffer.extend(subtrees.into_iter().flatten());
+        } else {
+            scan_for_links(&mut Cursor::new(block), |link| {
+                copy_rec(cache, link, depth + 1, buffer)
+            })?;
+        }
     }
 
     // Finally, push the block. We do this _last_ so that we always include write before parents.
@@ -269,8 +379,11 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use cid::multihash::{Code, Multihash};
-    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_blockstore::tracking::TrackingBlockstore;
+    use fvm_ipld_blockstore::{Block, Blockstore, MemoryBlockstore};
     use fvm_ipld_encoding::CborStore;
     use fvm_shared::{commcid, IDENTITY_HASH};
     use serde::{Deserialize, Serialize};
@@ -362,4 +475,93 @@ mod tests {
         assert_eq!(buf_store.get(&sealed_comm_cid).unwrap(), None);
         assert_eq!(mem.get_cbor::<u8>(&unconnected).unwrap(), None);
     }
+
+    #[test]
+    fn flush_skips_existing_blocks() {
+        let mem = MemoryBlockstore::default();
+        let existing = mem.put_cbor(&"existing", Code::Blake2b256).unwrap();
+        let tracking = TrackingBlockstore::new(&mem);
+        let buf_store = BufferedBlockstore::new(&tracking);
+
+        buf_store.put_cbor(&"existing", Code::Blake2b256).unwrap();
+        let leaf = buf_store.put_cbor(&"leaf", Code::Blake2b256).unwrap();
+        // Both children link to the same leaf.
+        let a = buf_store
+            .put_cbor(&(leaf, existing), Code::Blake2b256)
+            .unwrap();
+        let b = buf_store.put_cbor(&(leaf, 1u8), Code::Blake2b256).unwrap();
+        let root = buf_store.put_cbor(&(a, b), Code::Blake2b256).unwrap();
+
+        buf_store.flush(&root).unwrap();
+        assert_eq!(tracking.stats.borrow().w, 4);
+        assert_eq!(mem.get_cbor::<(Cid, Cid)>(&root).unwrap(), Some((a, b)));
+
+        // Nothing is written the second time around.
+        buf_store.flush(&root).unwrap();
+        assert_eq!(tracking.stats.borrow().w, 4);
+    }
+
+    /// Counts the batches written to the wrapped store.
+    struct BatchCountingBlockstore<BS> {
+        base: BS,
+        batches: Cell<usize>,
+    }
+
+    impl<BS: Blockstore> Blockstore for BatchCountingBlockstore<BS> {
+        fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+            self.base.get(k)
+        }
+
+        fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+            self.base.put_keyed(k, block)
+        }
+
+        fn has(&self, k: &Cid) -> Result<bool> {
+            self.base.has(k)
+        }
+
+        fn put_many_keyed<D, I>(&self, blocks: I) -> Result<()>
+        where
+            Self: Sized,
+            D: AsRef<[u8]>,
+            I: IntoIterator<Item = (Cid, D)>,
+        {
+            self.batches.set(self.batches.get() + 1);
+            self.base.put_many_keyed(blocks)
+        }
+    }
+
+    #[test]
+    fn flush_large_dag() {
+        let mem = MemoryBlockstore::default();
+        let counting = BatchCountingBlockstore {
+            base: &mem,
+            batches: Cell::new(0),
+        };
+        let buf_store = BufferedBlockstore::new(&counting);
+
+        // Deep enough to be traversed in parallel, and large enough to be written in multiple
+        // batches.
+        let mut level: Vec<Cid> = (0..64u8)
+            .map(|i| {
+                buf_store
+                    .put(Code::Blake2b256, &Block::new(RAW, vec![i; 256 << 10]))
+                    .unwrap()
+            })
+            .collect();
+        let mut all = level.clone();
+        while level.len() > 1 {
+            level = level
+                .chunks(4)
+                .map(|links| buf_store.put_cbor(&links, Code::Blake2b256).unwrap())
+                .collect();
+            all.extend(&level);
+        }
+
+        buf_store.flush(&level[0]).unwrap();
+        assert!(counting.batches.get() > 1);
+        for cid in all {
+            assert!(mem.has(&cid).unwrap());
+        }
+    }
 }
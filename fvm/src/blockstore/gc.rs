@@ -0,0 +1,188 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Mark-and-sweep garbage collection for in-memory blockstores.
+//!
+//! Only DAG-CBOR blocks are scanned for links (the same as [`Buffered::flush`]). Links to blocks
+//! the store doesn't hold, such as commitments or blocks only present in a base store, are
+//! considered reachable but not followed.
+//!
+//! [`Buffered::flush`]: fvm_ipld_blockstore::Buffered::flush
+
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use anyhow::Result;
+use cid::Cid;
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+use fvm_ipld_encoding::DAG_CBOR;
+
+use super::buffered::scan_for_links;
+
+/// The identity multihash code.
+const IDENTITY: u64 = 0x0;
+
+/// A blockstore that can list and remove the blocks it holds, and can therefore be garbage
+/// collected.
+pub trait Sweep: Blockstore {
+    /// Returns the CIDs of the blocks held by this store, in no particular order.
+    fn block_cids(&self) -> Vec<Cid>;
+
+    /// Removes the given blocks from this store. Missing blocks are ignored.
+    fn remove_blocks(&self, cids: &[Cid]) -> Result<()>;
+}
+
+impl Sweep for MemoryBlockstore {
+    fn block_cids(&self) -> Vec<Cid> {
+        self.cids()
+    }
+
+    fn remove_blocks(&self, cids: &[Cid]) -> Result<()> {
+        for cid in cids {
+            self.remove(cid);
+        }
+        Ok(())
+    }
+}
+
+impl<BS> Sweep for &BS
+where
+    BS: Sweep,
+{
+    fn block_cids(&self) -> Vec<Cid> {
+        (*self).block_cids()
+    }
+
+    fn remove_blocks(&self, cids: &[Cid]) -> Result<()> {
+        (*self).remove_blocks(cids)
+    }
+}
+
+/// Returns the set of blocks reachable from any of the `roots`, including the roots themselves.
+pub fn mark<BS, I>(store: &BS, roots: I) -> Result<HashSet<Cid>>
+where
+    BS: Sweep,
+    I: IntoIterator<Item = Cid>,
+{
+    let held: HashSet<Cid> = store.block_cids().into_iter().collect();
+    let mut reachable = HashSet::new();
+    let mut stack: Vec<Cid> = roots.into_iter().collect();
+    while let Some(cid) = stack.pop() {
+        if !reachable.insert(cid) || cid.codec() != DAG_CBOR {
+            continue;
+        }
+        let mut push = |link| {
+            stack.push(link);
+            Ok(())
+        };
+        if cid.hash().code() == IDENTITY {
+            scan_for_links(&mut Cursor::new(cid.hash().digest()), &mut push)?;
+        } else if held.contains(&cid) {
+            if let Some(block) = store.get(&cid)? {
+                scan_for_links(&mut Cursor::new(block), &mut push)?;
+            }
+        }
+    }
+    Ok(reachable)
+}
+
+/// Returns the blocks held by `store` that aren't reachable from any of the `roots`.
+pub fn find_unreachable<BS, I>(store: &BS, roots: I) -> Result<Vec<Cid>>
+where
+    BS: Sweep,
+    I: IntoIterator<Item = Cid>,
+{
+    let reachable = mark(store, roots)?;
+    Ok(store
+        .block_cids()
+        .into_iter()
+        .filter(|cid| !reachable.contains(cid))
+        .collect())
+}
+
+/// Removes all blocks from `store` that aren't reachable from any of the `roots`, returning the
+/// CIDs of the removed blocks.
+pub fn collect_garbage<BS, I>(store: &BS, roots: I) -> Result<Vec<Cid>>
+where
+    BS: Sweep,
+    I: IntoIterator<Item = Cid>,
+{
+    let unreachable = find_unreachable(store, roots)?;
+    store.remove_blocks(&unreachable)?;
+    Ok(unreachable)
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, Multihash};
+    use fvm_ipld_blockstore::Buffered;
+    use fvm_ipld_encoding::CborStore;
+
+    use super::*;
+    use crate::blockstore::BufferedBlockstore;
+
+    #[test]
+    fn collect_memory_blockstore() {
+        let store = MemoryBlockstore::new();
+        let leaf = store.put_cbor(&"leaf", Code::Blake2b256).unwrap();
+        let shared = store.put_cbor(&"shared", Code::Blake2b256).unwrap();
+        let node = store.put_cbor(&(leaf, shared), Code::Blake2b256).unwrap();
+        // Links inside identity CIDs are followed.
+        let inline = fvm_ipld_encoding::to_vec(&(shared,)).unwrap();
+        let inline = Cid::new_v1(DAG_CBOR, Multihash::wrap(IDENTITY, &inline).unwrap());
+        let other = store.put_cbor(&(inline, 1u8), Code::Blake2b256).unwrap();
+        let garbage = store.put_cbor(&(node, 2u8), Code::Blake2b256).unwrap();
+        let loose = store.put_cbor(&"loose", Code::Blake2b256).unwrap();
+
+        let reachable = mark(&store, [node, other]).unwrap();
+        assert_eq!(
+            reachable,
+            HashSet::from([node, leaf, shared, other, inline])
+        );
+
+        let mut unreachable = find_unreachable(&store, [node, other]).unwrap();
+        unreachable.sort();
+        let mut expected = vec![garbage, loose];
+        expected.sort();
+        assert_eq!(unreachable, expected);
+        assert!(store.has(&garbage).unwrap());
+
+        let mut removed = collect_garbage(&store, [other]).unwrap();
+        removed.sort();
+        let mut expected = vec![garbage, loose, node, leaf];
+        expected.sort();
+        assert_eq!(removed, expected);
+        let mut remaining = store.cids();
+        remaining.sort();
+        let mut expected = vec![other, shared];
+        expected.sort();
+        assert_eq!(remaining, expected);
+
+        assert_eq!(collect_garbage(&store, []).unwrap().len(), 2);
+        assert!(store.cids().is_empty());
+    }
+
+    #[test]
+    fn collect_buffered_blockstore() {
+        let mem = MemoryBlockstore::new();
+        let flushed = mem.put_cbor(&"flushed", Code::Blake2b256).unwrap();
+        let unflushed = mem.put_cbor(&"unflushed", Code::Blake2b256).unwrap();
+
+        let buf_store = BufferedBlockstore::new(&mem);
+        let leaf = buf_store.put_cbor(&"leaf", Code::Blake2b256).unwrap();
+        let root = buf_store
+            .put_cbor(&(leaf, flushed), Code::Blake2b256)
+            .unwrap();
+        let garbage = buf_store
+            .put_cbor(&(leaf, unflushed), Code::Blake2b256)
+            .unwrap();
+
+        // Only the write buffer is swept, the base store is left alone.
+        assert_eq!(collect_garbage(&buf_store, [root]).unwrap(), vec![garbage]);
+        assert!(!buf_store.has(&garbage).unwrap());
+        assert!(mem.has(&unflushed).unwrap());
+
+        buf_store.flush(&root).unwrap();
+        assert!(mem.has(&leaf).unwrap());
+        assert!(!mem.has(&garbage).unwrap());
+    }
+}
@@ -1,6 +1,10 @@
 // Copyright 2021-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
-//! Private blockstores for use in the FVM.
+//! Blockstores for use in the FVM.
 
 mod buffered;
+pub mod gc;
+mod sync_buffered;
+pub(crate) use buffered::scan_for_links;
 pub use buffered::BufferedBlockstore;
+pub use sync_buffered::SyncBufferedBlockstore;
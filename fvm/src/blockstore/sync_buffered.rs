@@ -0,0 +1,182 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use anyhow::Result;
+use cid::Cid;
+use fvm_ipld_blockstore::{Blockstore, Buffered};
+
+use super::buffered::{collect_blocks, retain_missing, write_blocks};
+use super::gc::Sweep;
+
+/// A thread-safe [`BufferedBlockstore`](super::BufferedBlockstore), for sharing one write buffer
+/// between threads. It's `Send + Sync` whenever the base store is.
+///
+/// Flushing writes the same blocks as the single-threaded version, but requires a thread-safe base
+/// store. Blocks written concurrently with a flush may or may not be flushed.
+#[derive(Debug)]
+pub struct SyncBufferedBlockstore<BS> {
+    base: BS,
+    write: RwLock<HashMap<Cid, Vec<u8>>>,
+}
+
+impl<BS> SyncBufferedBlockstore<BS>
+where
+    BS: Blockstore,
+{
+    pub fn new(base: BS) -> Self {
+        Self {
+            base,
+            write: Default::default(),
+        }
+    }
+
+    pub fn into_inner(self) -> BS {
+        self.base
+    }
+
+    // The buffer is never left half-modified, so we can ignore lock poisoning.
+    fn buffer(&self) -> RwLockReadGuard<'_, HashMap<Cid, Vec<u8>>> {
+        self.write.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn buffer_mut(&self) -> RwLockWriteGuard<'_, HashMap<Cid, Vec<u8>>> {
+        self.write.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<BS> Buffered for SyncBufferedBlockstore<BS>
+where
+    BS: Blockstore + Sync,
+{
+    /// Flushes the buffered cache based on the root node.
+    /// This will recursively traverse the cache and write all data connected by links to this
+    /// root Cid. Calling flush will not reset the write buffer.
+    ///
+    /// The reachable blocks are copied out of the buffer first, so other threads can keep
+    /// writing to the buffer while they're written to the base store. Unlike the single-threaded
+    /// version, the base store is checked for existing blocks in parallel.
+    fn flush(&self, root: &Cid) -> Result<()> {
+        let blocks: Vec<_> = collect_blocks(&self.buffer(), *root)?
+            .into_iter()
+            .map(|(cid, block)| (cid, block.to_vec()))
+            .collect();
+        write_blocks(&self.base, retain_missing(&self.base, blocks)?)
+    }
+}
+
+impl<BS> Sweep for SyncBufferedBlockstore<BS>
+where
+    BS: Blockstore,
+{
+    fn block_cids(&self) -> Vec<Cid> {
+        self.buffer().keys().copied().collect()
+    }
+
+    fn remove_blocks(&self, cids: &[Cid]) -> Result<()> {
+        let mut write = self.buffer_mut();
+        for cid in cids {
+            write.remove(cid);
+        }
+        Ok(())
+    }
+}
+
+impl<BS> Blockstore for SyncBufferedBlockstore<BS>
+where
+    BS: Blockstore,
+{
+    fn get(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+        if let Some(data) = self.buffer().get(cid) {
+            return Ok(Some(data.clone()));
+        }
+        self.base.get(cid)
+    }
+
+    fn put_keyed(&self, cid: &Cid, buf: &[u8]) -> Result<()> {
+        self.buffer_mut().insert(*cid, Vec::from(buf));
+        Ok(())
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        if self.buffer().contains_key(k) {
+            Ok(true)
+        } else {
+            self.base.has(k)
+        }
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        // Copy the blocks before taking the lock to keep the critical section short.
+        let blocks: Vec<_> = blocks
+            .into_iter()
+            .map(|(k, v)| (k, v.as_ref().to_vec()))
+            .collect();
+        self.buffer_mut().extend(blocks);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::CborStore;
+
+    use super::*;
+
+    /// A thread-safe base store.
+    #[derive(Default)]
+    struct LockedBlockstore(Mutex<MemoryBlockstore>);
+
+    impl Blockstore for LockedBlockstore {
+        fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+            self.0.lock().unwrap().get(k)
+        }
+
+        fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+            self.0.lock().unwrap().put_keyed(k, block)
+        }
+    }
+
+    #[test]
+    fn concurrent_buffered_store() {
+        let base = LockedBlockstore::default();
+        let buf_store = SyncBufferedBlockstore::new(&base);
+
+        let leaves: Vec<Cid> = std::thread::scope(|s| {
+            // Spawn all the writers before joining any of them.
+            #[allow(clippy::needless_collect)]
+            let handles: Vec<_> = (0..4u64)
+                .map(|i| {
+                    let buf_store = &buf_store;
+                    s.spawn(move || buf_store.put_cbor(&i, Code::Blake2b256).unwrap())
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let root = buf_store.put_cbor(&leaves, Code::Blake2b256).unwrap();
+        let unconnected = buf_store.put_cbor(&27u8, Code::Blake2b256).unwrap();
+        assert_eq!(base.get_cbor::<Vec<Cid>>(&root).unwrap(), None);
+
+        buf_store.flush(&root).unwrap();
+        assert_eq!(
+            base.get_cbor::<Vec<Cid>>(&root).unwrap(),
+            Some(leaves.clone())
+        );
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(base.get_cbor::<u64>(leaf).unwrap(), Some(i as u64));
+        }
+        assert_eq!(base.get_cbor::<u8>(&unconnected).unwrap(), None);
+        assert_eq!(buf_store.get_cbor::<u8>(&unconnected).unwrap(), Some(27));
+    }
+}
@@ -1,17 +1,19 @@
 // Copyright 2021-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::borrow::Cow;
 use std::fmt::Display;
 
 use fvm_shared::address::Address;
 use fvm_shared::error::{ErrorNumber, ExitCode};
 use fvm_shared::{ActorID, MethodNum};
+use serde::{Deserialize, Serialize};
 
 use crate::kernel::SyscallError;
 
 /// A call backtrace records the actors an error was propagated through, from
 /// the moment it was emitted. The original error is the _cause_. Backtraces are
 /// useful for identifying the root cause of an error.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Backtrace {
     /// The actors through which this error was propagated from bottom (source) to top.
     pub frames: Vec<Frame>,
@@ -72,7 +74,7 @@ impl Backtrace {
 }
 
 /// A "frame" in a call backtrace.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Frame {
     /// The actor that exited with this code.
     pub source: ActorID,
@@ -98,15 +100,16 @@ impl Display for Frame {
 }
 
 /// The ultimate "cause" of a failed message.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Cause {
     /// The original cause was a syscall error.
     Syscall {
         /// The syscall "module".
-        module: &'static str,
+        module: Cow<'static, str>,
         /// The syscall function name.
-        function: &'static str,
+        function: Cow<'static, str>,
         /// The exact syscall error.
+        #[serde(with = "crate::export::human::error_number")]
         error: ErrorNumber,
         /// The informational syscall message.
         message: String,
@@ -125,8 +128,8 @@ impl Cause {
     /// Records a failing syscall as the cause of a backtrace.
     pub fn from_syscall(module: &'static str, function: &'static str, err: SyscallError) -> Self {
         Self::Syscall {
-            module,
-            function,
+            module: module.into(),
+            function: function.into(),
             error: err.1,
             message: err.0,
         }
@@ -27,7 +27,7 @@ use crate::machine::Machine;
 use crate::state_tree::ActorState;
 use crate::syscalls::error::Abort;
 use crate::syscalls::{charge_for_exec, update_gas_available};
-use crate::trace::{ExecutionEvent, ExecutionTrace};
+use crate::trace::{ActorLog, CallTreeBuilder, ExecutionEvent, ExecutionTrace};
 use crate::{syscall_error, system_actor};
 
 /// The default [`CallManager`] implementation.
@@ -62,12 +62,16 @@ pub struct InnerDefaultCallManager<M: Machine> {
     backtrace: Backtrace,
     /// The current execution trace.
     exec_trace: ExecutionTrace,
+    /// The current execution trace, as a call tree.
+    call_tree: CallTreeBuilder,
     /// Number of actors that have been invoked in this message execution.
     invocation_count: u64,
     /// Limits on memory throughout the execution.
     limits: M::Limiter,
     /// Accumulator for events emitted in this call stack.
     events: EventsAccumulator,
+    /// Actor logs collected in this call stack, if enabled.
+    logs: Vec<ActorLog>,
 }
 
 #[doc(hidden)]
@@ -103,7 +107,8 @@ where
     ) -> Self {
         let limits = machine.new_limiter();
         let gas_tracker =
-            GasTracker::new(Gas::new(gas_limit), Gas::zero(), machine.context().tracing);
+            GasTracker::new(Gas::new(gas_limit), Gas::zero(), machine.context().tracing)
+                .with_observer(machine.context().observer.clone());
 
         DefaultCallManager(Some(Box::new(InnerDefaultCallManager {
             engine: Rc::new(engine),
@@ -117,9 +122,11 @@ where
             call_stack_depth: 0,
             backtrace: Backtrace::default(),
             exec_trace: vec![],
+            call_tree: CallTreeBuilder::default(),
             invocation_count: 0,
             limits,
             events: Default::default(),
+            logs: vec![],
         })))
     }
 
@@ -151,6 +158,9 @@ where
                 value: value.clone(),
             });
         }
+        if let Some(observer) = &self.machine.context().observer {
+            observer.on_call_start(from, &to, method, value);
+        }
 
         // If a specific gas limit has been requested, create a child GasTracker and use that
         // one hereon.
@@ -197,6 +207,9 @@ where
                 Err(ExecutionError::Syscall(s)) => ExecutionEvent::CallError(s.clone()),
             });
         }
+        if let Some(observer) = &self.machine.context().observer {
+            observer.on_call_end(&result);
+        }
 
         result
     }
@@ -230,7 +243,9 @@ where
             backtrace,
             gas_tracker,
             mut exec_trace,
+            mut call_tree,
             events,
+            logs,
             ..
         } = *self.0.take().expect("call manager is poisoned");
 
@@ -239,7 +254,12 @@ where
 
         // Finalize any trace events, if we're tracing.
         if machine.context().tracing {
-            exec_trace.extend(gas_tracker.drain_trace().map(ExecutionEvent::GasCharge));
+            for charge in gas_tracker.drain_trace() {
+                let event = ExecutionEvent::GasCharge(charge);
+                // Gas charges can't unbalance the call tree.
+                let _ = call_tree.push(event.clone());
+                exec_trace.push(event);
+            }
         }
 
         let events = events.finish();
@@ -249,7 +269,9 @@ where
                 gas_used,
                 backtrace,
                 exec_trace,
+                call_tree: call_tree.finish(),
                 events,
+                logs,
             },
             machine,
         )
@@ -348,14 +370,45 @@ where
         let t = self.charge_gas(self.price_list().on_create_actor(is_new))?;
         self.state_tree_mut().set_actor(actor_id, actor)?;
         self.num_actors_created += 1;
+        if let Some(observer) = &self.machine.context().observer {
+            observer.on_actor_created(actor_id, &code_id);
+        }
         t.stop_with(start);
         Ok(())
     }
 
     fn append_event(&mut self, evt: StampedEvent) {
+        if self.machine.context().tracing {
+            self.call_tree.event(evt.clone());
+        }
+        if let Some(observer) = &self.machine.context().observer {
+            observer.on_event(&evt);
+        }
         self.events.append_event(evt)
     }
 
+    fn trace(&mut self, event: ExecutionEvent) {
+        // The price of deref magic is that you sometimes need to tell the compiler: no, this is
+        // fine.
+        let s = &mut **self;
+
+        let events = s
+            .gas_tracker
+            .drain_trace()
+            .map(ExecutionEvent::GasCharge)
+            .chain(Some(event));
+        for event in events {
+            if let Err(e) = s.call_tree.push(event.clone()) {
+                log::error!("failed to trace call tree: {}", e);
+            }
+            s.exec_trace.push(event);
+        }
+    }
+
+    fn append_log(&mut self, log: ActorLog) {
+        self.logs.push(log)
+    }
+
     // Helper for creating actors. This really doesn't belong on this trait.
     fn invocation_count(&self) -> u64 {
         self.invocation_count
@@ -366,17 +419,6 @@ impl<M> DefaultCallManager<M>
 where
     M: Machine,
 {
-    fn trace(&mut self, trace: ExecutionEvent) {
-        // The price of deref magic is that you sometimes need to tell the compiler: no, this is
-        // fine.
-        let s = &mut **self;
-
-        s.exec_trace
-            .extend(s.gas_tracker.drain_trace().map(ExecutionEvent::GasCharge));
-
-        s.exec_trace.push(trace);
-    }
-
     fn create_account_actor<K>(&mut self, addr: &Address) -> Result<ActorID>
     where
         K: Kernel<CallManager = Self>,
@@ -391,9 +433,13 @@ where
 
         // Create the actor in the state tree.
         let id = {
-            let code_cid = self.builtin_actors().get_account_code();
-            let state = ActorState::new_empty(*code_cid, None);
-            self.machine.create_actor(addr, state)?
+            let code_cid = *self.builtin_actors().get_account_code();
+            let state = ActorState::new_empty(code_cid, None);
+            let id = self.machine.create_actor(addr, state)?;
+            if let Some(observer) = &self.machine.context().observer {
+                observer.on_actor_created(id, &code_cid);
+            }
+            id
         };
 
         // Now invoke the constructor; first create the parameters, then
@@ -429,10 +475,14 @@ where
         let t = self.charge_gas(self.price_list().on_create_actor(true))?;
 
         // Create the actor in the state tree, but don't call any constructor.
-        let code_cid = self.builtin_actors().get_placeholder_code();
+        let code_cid = *self.builtin_actors().get_placeholder_code();
 
-        let state = ActorState::new_empty(*code_cid, Some(*addr));
-        t.record(self.machine.create_actor(addr, state))
+        let state = ActorState::new_empty(code_cid, Some(*addr));
+        let res = self.machine.create_actor(addr, state);
+        if let (Ok(id), Some(observer)) = (&res, &self.machine.context().observer) {
+            observer.on_actor_created(*id, &code_cid);
+        }
+        t.record(res)
     }
 
     /// Send without checking the call depth.
@@ -502,10 +552,11 @@ where
             return Ok(InvocationResult::default());
         }
 
-        // Store the parametrs, and initialize the block registry for the target actor.
+        // Store the parametrs, and initialize the block registry for the target actor. Everything
+        // the parameters link to is reachable.
         let mut block_registry = BlockRegistry::new();
         let params_id = if let Some(blk) = params {
-            block_registry.put(blk)?
+            block_registry.put_reachable(blk)?
         } else {
             NO_DATA_BLOCK_ID
         };
@@ -688,10 +739,8 @@ where
         F: FnOnce(&mut Self) -> Result<V>,
     {
         if self.call_stack_depth >= self.machine.context().max_call_depth {
+            // The error is traced by the caller (`send`).
             let sys_err = syscall_error!(LimitExceeded, "message execution exceeds call depth");
-            if self.machine.context().tracing {
-                self.trace(ExecutionEvent::CallError(sys_err.clone()));
-            }
             return Err(sys_err.into());
         }
 
@@ -17,11 +17,13 @@ pub mod backtrace;
 pub use backtrace::Backtrace;
 
 mod default;
+mod observer;
 
 pub use default::DefaultCallManager;
 use fvm_shared::event::StampedEvent;
+pub use observer::ExecutionObserver;
 
-use crate::trace::ExecutionTrace;
+use crate::trace::{ActorLog, CallTree, ExecutionEvent, ExecutionTrace};
 
 /// BlockID representing nil parameters or return data.
 pub const NO_DATA_BLOCK_ID: u32 = 0;
@@ -118,7 +120,7 @@ pub trait CallManager: 'static {
 
     /// Returns the current price list.
     fn price_list(&self) -> &PriceList {
-        self.machine().context().price_list
+        &self.machine().context().price_list
     }
 
     /// Returns the machine context.
@@ -156,6 +158,14 @@ pub trait CallManager: 'static {
 
     /// Appends an event to the event accumulator.
     fn append_event(&mut self, evt: StampedEvent);
+
+    /// Appends an event to the execution trace. Callers should only do so when
+    /// [`MachineContext::tracing`] is enabled.
+    fn trace(&mut self, _event: ExecutionEvent) {}
+
+    /// Appends an actor log to the collected logs. Callers should only do so when
+    /// [`MachineContext::collect_actor_logs`] is enabled.
+    fn append_log(&mut self, _log: ActorLog) {}
 }
 
 /// The result of a method invocation.
@@ -181,5 +191,7 @@ pub struct FinishRet {
     pub gas_used: i64,
     pub backtrace: Backtrace,
     pub exec_trace: ExecutionTrace,
+    pub call_tree: CallTree,
     pub events: Vec<StampedEvent>,
+    pub logs: Vec<ActorLog>,
 }
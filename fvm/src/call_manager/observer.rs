@@ -0,0 +1,57 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::fmt;
+
+use cid::Cid;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::event::StampedEvent;
+use fvm_shared::{ActorID, MethodNum};
+
+use super::InvocationResult;
+use crate::gas::GasCharge;
+use crate::kernel::Result;
+
+/// Observes message execution as it happens, e.g., to collect metrics or profile gas usage.
+///
+/// Observers are registered with [`MachineContext::set_observer`](crate::machine::MachineContext::set_observer)
+/// and are invoked synchronously, so they should be cheap. All methods default to doing nothing.
+///
+/// Calls are strictly nested: every [`on_call_start`](Self::on_call_start) is followed by exactly
+/// one [`on_call_end`](Self::on_call_end), after any sub-calls have ended. Everything observed in
+/// between happened within that call (or one of its sub-calls).
+///
+/// Observers can't affect execution, but they do see things that never make it on-chain, like
+/// events that are later discarded because the emitting call failed.
+pub trait ExecutionObserver: Send + Sync + 'static {
+    /// Called before a call is made.
+    fn on_call_start(
+        &self,
+        _from: ActorID,
+        _to: &Address,
+        _method: MethodNum,
+        _value: &TokenAmount,
+    ) {
+    }
+
+    /// Called once a call has returned (or failed).
+    fn on_call_end(&self, _result: &Result<InvocationResult>) {}
+
+    /// Called whenever gas is charged, whether or not there was enough gas to cover the charge.
+    fn on_gas_charge(&self, _charge: &GasCharge) {}
+
+    /// Called before an actor invokes a syscall.
+    fn on_syscall(&self, _module: &'static str, _name: &'static str) {}
+
+    /// Called when an actor emits an event.
+    fn on_event(&self, _event: &StampedEvent) {}
+
+    /// Called when an actor is created (or a placeholder actor is deployed).
+    fn on_actor_created(&self, _actor_id: ActorID, _code: &Cid) {}
+}
+
+impl fmt::Debug for dyn ExecutionObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ExecutionObserver")
+    }
+}
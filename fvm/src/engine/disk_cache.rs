@@ -0,0 +1,223 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use cid::Cid;
+use wasmtime::Module;
+
+use super::{EngineConfig, ModuleRecord};
+
+/// Identifies the cache entry format.
+const MAGIC: &[u8; 8] = b"fvmmod\x00\x01";
+
+/// The length of the digests used to key the cache and check the integrity of entries.
+const DIGEST_LEN: usize = 32;
+
+/// The smallest valid Wasm module, compiled to fingerprint the engine.
+const EMPTY_WASM: &[u8] = b"\x00asm\x01\x00\x00\x00";
+
+/// A persistent cache of compiled Wasm modules.
+///
+/// Entries are stored under `<dir>/<key>/<code-cid>`, where `key` is derived from the
+/// [`EngineConfig`] and the wasmtime version and compilation settings. Each entry is checked
+/// against a digest before it's loaded, and stale or corrupt entries are ignored (and later
+/// overwritten).
+///
+/// The digest guards against corruption, not tampering: the cache directory must only be writable
+/// by trusted users, as loading a compiled module can execute arbitrary code.
+pub(super) struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Opens (creating, if necessary) the cache for the given engine in `dir`.
+    pub fn open(dir: &Path, engine: &wasmtime::Engine, ec: &EngineConfig) -> anyhow::Result<Self> {
+        let mut hasher = DigestHasher::default();
+
+        // Destructure so we don't forget to update this when adding new fields.
+        let EngineConfig {
+            max_call_depth,
+            max_wasm_stack,
+            max_inst_memory_bytes,
+            concurrency,
+            wasm_prices,
+            actor_redirect,
+            // Where the cache lives and how many modules are kept in memory don't affect the
+            // cached modules.
+            module_cache_dir: _,
+            module_cache_max_bytes: _,
+        } = ec;
+        max_call_depth.hash(&mut hasher);
+        max_wasm_stack.hash(&mut hasher);
+        max_inst_memory_bytes.hash(&mut hasher);
+        concurrency.hash(&mut hasher);
+        wasm_prices.hash(&mut hasher);
+        actor_redirect.hash(&mut hasher);
+
+        // Precompiled modules embed the wasmtime version along with the compilation settings
+        // (target, compiler flags, enabled features, etc.), so an empty module serves as a
+        // fingerprint of the engine.
+        let fingerprint = engine
+            .precompile_module(EMPTY_WASM)
+            .context("failed to fingerprint the wasm engine")?;
+        hasher.write(&fingerprint);
+
+        let dir = dir.join(hasher.digest().to_hex().as_str());
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create module cache {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Loads a compiled module from the cache, returning `None` if it's missing or invalid.
+    pub fn load(&self, engine: &wasmtime::Engine, k: &Cid) -> Option<ModuleRecord> {
+        let path = self.path(k);
+        let entry = fs::read(&path).ok()?;
+        let (size, compiled) = match decode_entry(&entry) {
+            Some(v) => v,
+            None => {
+                log::warn!("ignoring corrupt module cache entry {}", path.display());
+                return None;
+            }
+        };
+        // SAFETY: The entry was written by `DiskCache::store` (modulo the integrity check above),
+        // from a module serialized by `Module::serialize`. Modules compiled by a different
+        // version of wasmtime, or with different settings, are rejected by `Module::deserialize`.
+        match unsafe { Module::deserialize(engine, compiled) } {
+            Ok(module) => Some(ModuleRecord { module, size }),
+            Err(e) => {
+                log::warn!(
+                    "ignoring stale module cache entry {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Writes a compiled module to the cache. Failures are logged and otherwise ignored.
+    pub fn store(&self, k: &Cid, record: &ModuleRecord) {
+        if let Err(e) = self.try_store(k, record) {
+            log::warn!("failed to write module {} to the module cache: {:#}", k, e);
+        }
+    }
+
+    fn try_store(&self, k: &Cid, record: &ModuleRecord) -> anyhow::Result<()> {
+        let entry = encode_entry(record.size, &record.module.serialize()?);
+
+        // Write to a temporary file and rename it into place so concurrent readers (and writers)
+        // never observe a partial entry.
+        let path = self.path(k);
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&entry)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path).map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            e.into()
+        })
+    }
+
+    fn path(&self, k: &Cid) -> PathBuf {
+        self.dir.join(k.to_string())
+    }
+}
+
+/// A [`Hasher`] producing a cryptographic digest, so cache keys are stable across runs.
+struct DigestHasher(blake2b_simd::State);
+
+impl Default for DigestHasher {
+    fn default() -> Self {
+        DigestHasher(
+            blake2b_simd::Params::new()
+                .hash_length(DIGEST_LEN)
+                .to_state(),
+        )
+    }
+}
+
+impl DigestHasher {
+    fn digest(&self) -> blake2b_simd::Hash {
+        self.0.finalize()
+    }
+}
+
+impl Hasher for DigestHasher {
+    fn finish(&self) -> u64 {
+        let digest = self.digest();
+        u64::from_le_bytes(digest.as_bytes()[..8].try_into().unwrap())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+}
+
+/// Encodes a cache entry as `MAGIC || size || digest || compiled`, where `size` is the size of
+/// the instrumented Wasm (little-endian) and `digest` covers both the size and the compiled module.
+fn encode_entry(size: usize, compiled: &[u8]) -> Vec<u8> {
+    let size = (size as u64).to_le_bytes();
+    let mut hasher = DigestHasher::default();
+    hasher.write(&size);
+    hasher.write(compiled);
+
+    let mut entry = Vec::with_capacity(MAGIC.len() + size.len() + DIGEST_LEN + compiled.len());
+    entry.extend_from_slice(MAGIC);
+    entry.extend_from_slice(&size);
+    entry.extend_from_slice(hasher.digest().as_bytes());
+    entry.extend_from_slice(compiled);
+    entry
+}
+
+/// Decodes a cache entry, returning `None` if it's malformed or corrupt.
+fn decode_entry(entry: &[u8]) -> Option<(usize, &[u8])> {
+    let entry = entry.strip_prefix(MAGIC)?;
+    if entry.len() < 8 + DIGEST_LEN {
+        return None;
+    }
+    let (size, entry) = entry.split_at(8);
+    let (digest, compiled) = entry.split_at(DIGEST_LEN);
+
+    let mut hasher = DigestHasher::default();
+    hasher.write(size);
+    hasher.write(compiled);
+    if hasher.digest().as_bytes() != digest {
+        return None;
+    }
+
+    let size = u64::from_le_bytes(size.try_into().unwrap());
+    Some((size.try_into().ok()?, compiled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_roundtrip() {
+        let entry = encode_entry(1234, b"compiled");
+        assert_eq!(decode_entry(&entry), Some((1234, &b"compiled"[..])));
+        assert_eq!(decode_entry(&encode_entry(0, b"")), Some((0, &b""[..])));
+    }
+
+    #[test]
+    fn entry_corrupt() {
+        let entry = encode_entry(1234, b"compiled");
+
+        // Every single-bit flip is detected.
+        for i in 0..entry.len() {
+            let mut corrupt = entry.clone();
+            corrupt[i] ^= 1;
+            assert_eq!(decode_entry(&corrupt), None, "flipped byte {}", i);
+        }
+
+        // As is truncation.
+        for len in 0..entry.len() {
+            assert_eq!(decode_entry(&entry[..len]), None, "truncated to {}", len);
+        }
+    }
+}
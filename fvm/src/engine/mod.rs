@@ -4,6 +4,7 @@ use std::any::{Any, TypeId};
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::sync::{Arc, Condvar, Mutex};
 
 use anyhow::{anyhow, Context};
@@ -26,10 +27,18 @@ use crate::syscalls::error::Abort;
 use crate::syscalls::{bind_syscalls, charge_for_init, record_init_time, InvocationData};
 use crate::Kernel;
 
+mod disk_cache;
+mod module_cache;
+use disk_cache::DiskCache;
+use module_cache::ModuleCache;
+pub use module_cache::ModuleCacheStats;
+
 /// Container managing engines with different consensus-affecting configurations.
 pub struct MultiEngine {
     engines: Mutex<HashMap<EngineConfig, EnginePool>>,
     concurrency: u32,
+    module_cache_dir: Option<PathBuf>,
+    module_cache_max_bytes: Option<usize>,
 }
 
 /// The proper way of getting this struct is to convert from `NetworkConfig`
@@ -39,8 +48,19 @@ pub struct EngineConfig {
     pub max_wasm_stack: u32,
     pub max_inst_memory_bytes: u64,
     pub concurrency: u32,
-    pub wasm_prices: &'static WasmGasPrices,
+    pub wasm_prices: WasmGasPrices,
     pub actor_redirect: Vec<(Cid, Cid)>,
+    /// A directory in which to persist compiled Wasm modules across restarts. Modules are loaded
+    /// from this cache, if present, instead of being recompiled.
+    ///
+    /// DEFAULT: `None` (disabled)
+    pub module_cache_dir: Option<PathBuf>,
+    /// The maximum total size of the compiled Wasm modules cached in memory, in bytes. Once
+    /// exceeded, the least recently used modules are evicted. Pinned modules (e.g., the builtin
+    /// actors) are never evicted and don't count towards this limit.
+    ///
+    /// DEFAULT: `None` (unbounded)
+    pub module_cache_max_bytes: Option<usize>,
 }
 
 impl From<&NetworkConfig> for EngineConfig {
@@ -49,9 +69,11 @@ impl From<&NetworkConfig> for EngineConfig {
             max_call_depth: nc.max_call_depth,
             max_wasm_stack: nc.max_wasm_stack,
             max_inst_memory_bytes: nc.max_inst_memory_bytes,
-            wasm_prices: &nc.price_list.wasm_rules,
+            wasm_prices: nc.price_list.wasm_rules.clone(),
             actor_redirect: nc.actor_redirect.clone(),
             concurrency: 1,
+            module_cache_dir: None,
+            module_cache_max_bytes: None,
         }
     }
 }
@@ -64,9 +86,25 @@ impl MultiEngine {
         MultiEngine {
             engines: Mutex::new(HashMap::new()),
             concurrency,
+            module_cache_dir: None,
+            module_cache_max_bytes: None,
         }
     }
 
+    /// Persist compiled Wasm modules in the given directory. See
+    /// [`EngineConfig::module_cache_dir`].
+    pub fn with_module_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.module_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Limit the total size of the compiled Wasm modules cached in memory. See
+    /// [`EngineConfig::module_cache_max_bytes`].
+    pub fn with_module_cache_limit(mut self, max_bytes: usize) -> Self {
+        self.module_cache_max_bytes = Some(max_bytes);
+        self
+    }
+
     pub fn get(&self, nc: &NetworkConfig) -> anyhow::Result<EnginePool> {
         let mut engines = self
             .engines
@@ -75,6 +113,8 @@ impl MultiEngine {
 
         let mut ec: EngineConfig = nc.into();
         ec.concurrency = self.concurrency;
+        ec.module_cache_dir = self.module_cache_dir.clone();
+        ec.module_cache_max_bytes = self.module_cache_max_bytes;
 
         let pool = match engines.entry(ec.clone()) {
             Occupied(entry) => entry.into_mut(),
@@ -199,6 +239,13 @@ struct ModuleRecord {
     size: usize,
 }
 
+impl ModuleRecord {
+    /// Returns the size of the compiled module, in bytes.
+    fn compiled_size(&self) -> usize {
+        self.module.image_range().len()
+    }
+}
+
 struct EngineInner {
     limit: Mutex<u32>,
     condv: Condvar,
@@ -213,7 +260,8 @@ struct EngineInner {
     dummy_gas_global: Global,
     dummy_memory: Memory,
 
-    module_cache: Mutex<HashMap<Cid, ModuleRecord>>,
+    module_cache: Mutex<ModuleCache<ModuleRecord>>,
+    disk_cache: Option<DiskCache>,
     instance_cache: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
     config: EngineConfig,
 
@@ -236,6 +284,16 @@ impl EnginePool {
         Engine(self.0.clone())
     }
 
+    /// Pins the given actor code in the module cache shared by this pool's engines, so that it's
+    /// never evicted (e.g., because it's builtin actor code). The code doesn't need to have been
+    /// loaded yet, and no engine needs to be acquired.
+    pub fn pin_modules<'a>(&self, cids: impl IntoIterator<Item = &'a Cid>) {
+        let mut cache = self.0.module_cache.lock().expect("module_cache poisoned");
+        for cid in cids {
+            cache.pin(*self.0.actor_redirect.get(cid).unwrap_or(cid));
+        }
+    }
+
     /// Try to acquire an [`Engine`]. Returns `None` if the call would block, or if the lock is
     /// poisoned.
     ///
@@ -270,13 +328,20 @@ impl EnginePool {
 
         let actor_redirect = ec.actor_redirect.iter().cloned().collect();
 
+        let disk_cache = ec
+            .module_cache_dir
+            .as_deref()
+            .map(|dir| DiskCache::open(dir, &engine, &ec))
+            .transpose()?;
+
         Ok(EnginePool(Arc::new(EngineInner {
             limit: Mutex::new(ec.concurrency),
             condv: Condvar::new(),
             engine,
             dummy_memory,
             dummy_gas_global: dummy_gg,
-            module_cache: Default::default(),
+            module_cache: Mutex::new(ModuleCache::new(ec.module_cache_max_bytes)),
+            disk_cache,
             instance_cache: Mutex::new(HashMap::new()),
             config: ec,
             actor_redirect,
@@ -322,23 +387,20 @@ impl Engine {
         blockstore: BS,
     ) -> anyhow::Result<usize> {
         let code_cid = self.with_redirect(code_cid);
-        if let Some(item) = self
-            .0
-            .module_cache
-            .lock()
-            .expect("module_cache poisoned")
-            .get(code_cid)
-        {
-            return Ok(item.size);
-        }
-        let wasm = blockstore.get(code_cid)?.ok_or_else(|| {
-            anyhow!(
-                "no wasm bytecode in blockstore for CID {}",
-                &code_cid.to_string()
-            )
-        })?;
-        // compile and cache instantiated WASM module
-        self.prepare_wasm_bytecode(code_cid, &wasm)
+        // compile (or load from the module cache) and cache instantiated WASM module
+        let record = self
+            .get_or_load(
+                code_cid,
+                || blockstore.has(code_cid),
+                || blockstore.get(code_cid),
+            )?
+            .ok_or_else(|| {
+                anyhow!(
+                    "no wasm bytecode in blockstore for CID {}",
+                    &code_cid.to_string()
+                )
+            })?;
+        Ok(record.size)
     }
 
     /// Instantiates and caches the Wasm modules for the bytecodes addressed by
@@ -373,17 +435,70 @@ impl Engine {
     /// Loads some Wasm code into the engine and prepares it for execution.
     pub fn prepare_wasm_bytecode(&self, k: &Cid, wasm: &[u8]) -> anyhow::Result<usize> {
         let k = self.with_redirect(k);
-        let mut cache = self.0.module_cache.lock().expect("module_cache poisoned");
-        let size = match cache.get(k) {
-            Some(item) => item.size,
-            None => {
-                let m = self.load_raw(wasm)?;
-                let s = m.size;
-                cache.insert(*k, m);
-                s
-            }
+        let record = self
+            .get_or_load(k, || Ok(true), || Ok(Some(wasm.to_vec())))?
+            .expect("wasm is present");
+        Ok(record.size)
+    }
+
+    /// Looks up a module in the in-memory module cache, or loads it with
+    /// [`load_or_compile`](Self::load_or_compile) and caches it. The cache isn't locked while
+    /// loading, so that modules can be compiled concurrently.
+    fn get_or_load(
+        &self,
+        k: &Cid,
+        has_wasm: impl FnOnce() -> anyhow::Result<bool>,
+        get_wasm: impl FnOnce() -> anyhow::Result<Option<Vec<u8>>>,
+    ) -> anyhow::Result<Option<ModuleRecord>> {
+        let cached = self
+            .0
+            .module_cache
+            .lock()
+            .expect("module_cache poisoned")
+            .get(k);
+        if cached.is_some() {
+            return Ok(cached);
+        }
+        Ok(self.load_or_compile(k, has_wasm, get_wasm)?.map(|record| {
+            let bytes = record.compiled_size();
+            self.0
+                .module_cache
+                .lock()
+                .expect("module_cache poisoned")
+                .get_or_insert(*k, record, bytes)
+        }))
+    }
+
+    /// Loads a module from the persistent module cache, if enabled. Otherwise, compiles the Wasm
+    /// returned by `get_wasm` and adds it to the persistent module cache.
+    ///
+    /// The persistent module cache is only consulted if `has_wasm` returns true: whether some code
+    /// can be loaded must never depend on node-local state.
+    ///
+    /// Returns `None` if `has_wasm` returns false, or if the module isn't cached and `get_wasm`
+    /// returns `None`.
+    fn load_or_compile(
+        &self,
+        k: &Cid,
+        has_wasm: impl FnOnce() -> anyhow::Result<bool>,
+        get_wasm: impl FnOnce() -> anyhow::Result<Option<Vec<u8>>>,
+    ) -> anyhow::Result<Option<ModuleRecord>> {
+        if !has_wasm()? {
+            return Ok(None);
+        }
+        let disk_cache = self.0.disk_cache.as_ref();
+        if let Some(record) = disk_cache.and_then(|c| c.load(&self.0.engine, k)) {
+            return Ok(Some(record));
+        }
+        let wasm = match get_wasm()? {
+            Some(wasm) => wasm,
+            None => return Ok(None),
         };
-        Ok(size)
+        let record = self.load_raw(&wasm)?;
+        if let Some(c) = disk_cache {
+            c.store(k, &record);
+        }
+        Ok(Some(record))
     }
 
     fn load_raw(&self, raw_wasm: &[u8]) -> anyhow::Result<ModuleRecord> {
@@ -415,7 +530,7 @@ impl Engine {
         //   (code `0xFC 15`) uses what parity-wasm calls the `BULK_PREFIX` but it was added later in
         //   https://github.com/WebAssembly/reference-types/issues/29 and is not recognised by the
         //   parity-wasm module parser, so the contract cannot grow the tables.
-        let raw_wasm = gas_metering::inject(&raw_wasm, self.0.config.wasm_prices, "gas")
+        let raw_wasm = gas_metering::inject(&raw_wasm, &self.0.config.wasm_prices, "gas")
             .map_err(|_| anyhow::Error::msg("injecting gas counter failed"))?;
 
         let module = Module::from_binary(&self.0.engine, &raw_wasm)?;
@@ -437,15 +552,12 @@ impl Engine {
         let module = match cache.get(k) {
             Some(m) => m.module.clone(),
             None => {
-                let module = Module::deserialize(&self.0.engine, compiled)?;
-                cache.insert(
-                    *k,
-                    ModuleRecord {
-                        module: module.clone(),
-                        size: compiled.len(),
-                    },
-                );
-                module
+                let record = ModuleRecord {
+                    module: Module::deserialize(&self.0.engine, compiled)?,
+                    size: compiled.len(),
+                };
+                cache.insert(*k, record.clone(), record.compiled_size());
+                record.module
             }
         };
         Ok(module)
@@ -458,20 +570,17 @@ impl Engine {
         k: &Cid,
     ) -> anyhow::Result<Option<Module>> {
         let k = self.with_redirect(k);
-        match self
-            .0
-            .module_cache
-            .lock()
-            .expect("module_cache poisoned")
-            .entry(*k)
-        {
-            Occupied(v) => Ok(Some(v.get().module.clone())),
-            Vacant(v) => blockstore
-                .get(k)
-                .context("failed to lookup wasm module in blockstore")?
-                .map(|raw_wasm| Ok(v.insert(self.load_raw(&raw_wasm)?).module.clone()))
-                .transpose(),
-        }
+        Ok(self
+            .get_or_load(
+                k,
+                || blockstore.has(k),
+                || {
+                    blockstore
+                        .get(k)
+                        .context("failed to lookup wasm module in blockstore")
+                },
+            )?
+            .map(|record| record.module))
     }
 
     /// Lookup and instantiate a loaded wasmtime module with the given store. This will cache the
@@ -509,8 +618,6 @@ impl Engine {
             .context("failed to define gas counter")
             .map_err(Abort::Fatal)?;
 
-        let mut module_cache = self.0.module_cache.lock().expect("module_cache poisoned");
-
         let instantiate = |store: &mut wasmtime::Store<InvocationData<K>>, module| {
             // Before we instantiate the module, we should make sure the user has sufficient gas to
             // pay for the minimum memory requirements. The module instrumentation in `inject` only
@@ -554,27 +661,33 @@ impl Engine {
             Ok(Some(inst))
         };
 
-        match module_cache.entry(*k) {
-            Occupied(v) => instantiate(store, &v.get().module),
-            Vacant(v) => match store
-                .data()
-                .kernel
-                .machine()
-                .blockstore()
-                .get(k)
-                .context("failed to lookup wasm module in blockstore")
-                .map_err(Abort::Fatal)?
-            {
-                Some(raw_wasm) => instantiate(
-                    store,
-                    &v.insert(self.load_raw(&raw_wasm).map_err(Abort::Fatal)?)
-                        .module,
-                ),
-                None => Ok(None),
-            },
+        let blockstore = store.data().kernel.machine().blockstore();
+        let record = self
+            .get_or_load(
+                k,
+                || blockstore.has(k),
+                || {
+                    blockstore
+                        .get(k)
+                        .context("failed to lookup wasm module in blockstore")
+                },
+            )
+            .map_err(Abort::Fatal)?;
+        match record {
+            Some(record) => instantiate(store, &record.module),
+            None => Ok(None),
         }
     }
 
+    /// Returns the module cache's statistics. These are shared by all engines in the pool.
+    pub fn module_cache_stats(&self) -> ModuleCacheStats {
+        self.0
+            .module_cache
+            .lock()
+            .expect("module_cache poisoned")
+            .stats()
+    }
+
     /// Construct a new wasmtime "store" from the given kernel.
     pub fn new_store<K: Kernel>(&self, mut kernel: K) -> wasmtime::Store<InvocationData<K>> {
         let memory_bytes = kernel.limiter_mut().memory_used();
@@ -642,10 +755,26 @@ impl<L: MemoryLimiter> wasmtime::ResourceLimiter for WasmtimeLimiter<L> {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
+    use cid::multihash::{Code, MultihashDigest};
+    use cid::Cid;
+    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_encoding::IPLD_RAW;
+    use fvm_shared::version::NetworkVersion;
     use wasmtime::ResourceLimiter;
 
-    use crate::engine::WasmtimeLimiter;
+    use crate::engine::{EngineConfig, EnginePool, WasmtimeLimiter};
     use crate::machine::limiter::MemoryLimiter;
+    use crate::machine::NetworkConfig;
+
+    /// A module with a single empty function.
+    const WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: () -> ()
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+    ];
 
     #[derive(Default)]
     struct Limiter {
@@ -699,4 +828,30 @@ mod tests {
         assert!(limits.table_growing(2, 4, None));
         assert_eq!(limits.0.memory, 5 * 8);
     }
+
+    #[test]
+    fn disk_cache_requires_code() {
+        let dir = std::env::temp_dir().join(format!("fvm-module-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let nc = NetworkConfig::new(NetworkVersion::V18);
+        let mut ec: EngineConfig = (&nc).into();
+        ec.module_cache_dir = Some(dir.clone());
+        let code = Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(WASM));
+
+        // Compile the module, adding it to the disk cache.
+        let engine = EnginePool::new_default(ec.clone()).unwrap().acquire();
+        let size = engine.prepare_wasm_bytecode(&code, WASM).unwrap();
+
+        // A fresh engine must not load the module from the disk cache if the code is missing
+        // from the blockstore.
+        let engine = EnginePool::new_default(ec).unwrap().acquire();
+        let bs = MemoryBlockstore::default();
+        assert!(engine.prepare_actor_code(&code, &bs).is_err());
+
+        bs.put_keyed(&code, WASM).unwrap();
+        assert_eq!(engine.prepare_actor_code(&code, &bs).unwrap(), size);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,241 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use cid::Cid;
+
+/// Module cache statistics, as returned by [`Engine::module_cache_stats`](super::Engine::module_cache_stats).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ModuleCacheStats {
+    /// The number of lookups that found a cached module.
+    pub hits: u64,
+    /// The number of lookups that didn't find a cached module.
+    pub misses: u64,
+    /// The number of modules evicted to stay within the limit.
+    pub evictions: u64,
+    /// The number of modules currently cached.
+    pub modules: usize,
+    /// The total size of the currently cached (compiled) modules, in bytes.
+    pub bytes: usize,
+}
+
+struct Entry<V> {
+    value: V,
+    bytes: usize,
+    /// When the entry was last used, or `None` if it's pinned.
+    last_used: Option<u64>,
+}
+
+/// A cache of compiled modules with least-recently-used eviction, bounded by the total size of the
+/// cached modules. Pinned modules are never evicted (and don't count towards the limit).
+pub(super) struct ModuleCache<V> {
+    entries: HashMap<Cid, Entry<V>>,
+    /// Unpinned entries by when they were last used.
+    lru: BTreeMap<u64, Cid>,
+    pinned: HashSet<Cid>,
+    max_bytes: Option<usize>,
+    unpinned_bytes: usize,
+    tick: u64,
+    stats: ModuleCacheStats,
+}
+
+impl<V: Clone> ModuleCache<V> {
+    /// Creates a new cache, evicting modules once their total size exceeds `max_bytes` (if
+    /// specified).
+    pub fn new(max_bytes: Option<usize>) -> Self {
+        ModuleCache {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            pinned: HashSet::new(),
+            max_bytes,
+            unpinned_bytes: 0,
+            tick: 0,
+            stats: ModuleCacheStats::default(),
+        }
+    }
+
+    /// Looks up a module, marking it as recently used.
+    pub fn get(&mut self, k: &Cid) -> Option<V> {
+        let entry = match self.entries.get_mut(k) {
+            Some(entry) => entry,
+            None => {
+                self.stats.misses += 1;
+                return None;
+            }
+        };
+        self.stats.hits += 1;
+        if let Some(last_used) = &mut entry.last_used {
+            self.lru.remove(last_used);
+            self.tick += 1;
+            *last_used = self.tick;
+            self.lru.insert(self.tick, *k);
+        }
+        Some(entry.value.clone())
+    }
+
+    /// Caches a module of the given size, replacing any existing entry, then evicts the least
+    /// recently used modules (other than this one) until the cache is back within its limit.
+    pub fn insert(&mut self, k: Cid, value: V, bytes: usize) {
+        self.remove(&k);
+
+        let last_used = if self.pinned.contains(&k) {
+            None
+        } else {
+            self.tick += 1;
+            self.lru.insert(self.tick, k);
+            self.unpinned_bytes += bytes;
+            Some(self.tick)
+        };
+        self.entries.insert(
+            k,
+            Entry {
+                value,
+                bytes,
+                last_used,
+            },
+        );
+        self.stats.bytes += bytes;
+        self.stats.modules += 1;
+
+        let max_bytes = match self.max_bytes {
+            Some(max) => max,
+            None => return,
+        };
+        while self.unpinned_bytes > max_bytes {
+            let oldest = match self.lru.values().next() {
+                Some(oldest) if *oldest != k => *oldest,
+                _ => break,
+            };
+            log::debug!("evicting module {} from the module cache", oldest);
+            self.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+    }
+
+    /// Caches a module like [`ModuleCache::insert`], unless it's already cached (e.g., because
+    /// another thread compiled it concurrently). Returns the cached module.
+    pub fn get_or_insert(&mut self, k: Cid, value: V, bytes: usize) -> V {
+        if let Some(entry) = self.entries.get(&k) {
+            return entry.value.clone();
+        }
+        self.insert(k, value.clone(), bytes);
+        value
+    }
+
+    /// Pins a module so that it's never evicted. The module doesn't need to be cached yet.
+    pub fn pin(&mut self, k: Cid) {
+        if !self.pinned.insert(k) {
+            return;
+        }
+        if let Some(entry) = self.entries.get_mut(&k) {
+            if let Some(last_used) = entry.last_used.take() {
+                self.lru.remove(&last_used);
+                self.unpinned_bytes -= entry.bytes;
+            }
+        }
+    }
+
+    /// Returns the cache's statistics.
+    pub fn stats(&self) -> ModuleCacheStats {
+        self.stats
+    }
+
+    fn remove(&mut self, k: &Cid) {
+        if let Some(entry) = self.entries.remove(k) {
+            if let Some(last_used) = entry.last_used {
+                self.lru.remove(&last_used);
+                self.unpinned_bytes -= entry.bytes;
+            }
+            self.stats.bytes -= entry.bytes;
+            self.stats.modules -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+    use fvm_ipld_encoding::IPLD_RAW;
+
+    use super::*;
+
+    fn cid(i: u8) -> Cid {
+        Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(&[i]))
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = ModuleCache::new(Some(30));
+        cache.insert(cid(1), 1, 10);
+        cache.insert(cid(2), 2, 10);
+        cache.insert(cid(3), 3, 10);
+
+        // Use 1, so 2 is evicted next.
+        assert_eq!(cache.get(&cid(1)), Some(1));
+        cache.insert(cid(4), 4, 10);
+        assert_eq!(cache.get(&cid(2)), None);
+        assert_eq!(cache.get(&cid(1)), Some(1));
+
+        // A module larger than the limit evicts everything else, but is kept itself.
+        cache.insert(cid(5), 5, 100);
+        assert_eq!(cache.get(&cid(5)), Some(5));
+        for i in [1, 3, 4] {
+            assert_eq!(cache.get(&cid(i)), None);
+        }
+
+        assert_eq!(
+            cache.stats(),
+            ModuleCacheStats {
+                hits: 3,
+                misses: 4,
+                evictions: 4,
+                modules: 1,
+                bytes: 100,
+            }
+        );
+    }
+
+    #[test]
+    fn pinned() {
+        let mut cache = ModuleCache::new(Some(10));
+        // Pin one module before it's cached, and another after.
+        cache.pin(cid(1));
+        cache.insert(cid(1), 1, 10);
+        cache.insert(cid(2), 2, 10);
+        cache.pin(cid(2));
+
+        // Pinned modules don't count towards the limit.
+        cache.insert(cid(3), 3, 10);
+        cache.insert(cid(4), 4, 10);
+        assert_eq!(cache.get(&cid(3)), None);
+        for i in [1, 2, 4] {
+            assert_eq!(cache.get(&cid(i)), Some(i));
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.modules, 3);
+        assert_eq!(stats.bytes, 30);
+    }
+
+    #[test]
+    fn get_or_insert() {
+        let mut cache = ModuleCache::new(None);
+        assert_eq!(cache.get_or_insert(cid(1), 1, 10), 1);
+        // The first module cached wins.
+        assert_eq!(cache.get_or_insert(cid(1), 2, 10), 1);
+        assert_eq!(cache.get(&cid(1)), Some(1));
+        assert_eq!(cache.stats().modules, 1);
+        assert_eq!(cache.stats().bytes, 10);
+    }
+
+    #[test]
+    fn unbounded() {
+        let mut cache = ModuleCache::new(None);
+        for i in 0..100 {
+            cache.insert(cid(i), i, 1 << 20);
+        }
+        assert_eq!(cache.stats().modules, 100);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+}
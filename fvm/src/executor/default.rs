@@ -15,14 +15,14 @@ use fvm_shared::receipt::Receipt;
 use fvm_shared::{ActorID, IPLD_RAW, METHOD_SEND};
 use num_traits::Zero;
 
-use super::{ApplyFailure, ApplyKind, ApplyRet, Executor};
+use super::{ApplyFailure, ApplyKind, ApplyRet, EstimateOptions, Executor, GasEstimate};
 use crate::call_manager::{backtrace, Backtrace, CallManager, InvocationResult};
 use crate::eam_actor::EAM_ACTOR_ID;
 use crate::engine::EnginePool;
-use crate::gas::{Gas, GasCharge, GasOutputs};
+use crate::gas::{max_gas_limit_without_burn, Gas, GasCharge, GasOutputs};
 use crate::kernel::{Block, ClassifyResult, Context as _, ExecutionError, Kernel};
 use crate::machine::{Machine, BURNT_FUNDS_ACTOR_ID, REWARD_ACTOR_ID};
-use crate::trace::ExecutionTrace;
+use crate::trace::{ActorLog, CallTree, ExecutionTrace};
 
 /// The default [`Executor`].
 ///
@@ -63,10 +63,88 @@ where
         msg: Message,
         apply_kind: ApplyKind,
         raw_length: usize,
+    ) -> anyhow::Result<ApplyRet> {
+        self.apply_message(msg, apply_kind, raw_length, &EstimateOptions::default())
+    }
+
+    /// Estimate the gas required by a message, reverting all state changes afterwards.
+    fn estimate_message(
+        &mut self,
+        msg: Message,
+        raw_length: usize,
+        options: EstimateOptions,
+    ) -> anyhow::Result<GasEstimate> {
+        // Apply the message inside a state-tree transaction that we always revert. This undoes the
+        // sender's nonce/balance updates, the gas payouts, and any changes made by the message
+        // itself.
+        self.state_tree_mut().begin_transaction(false);
+        let res = self.apply_message(msg, ApplyKind::Explicit, raw_length, &options);
+        self.state_tree_mut().end_transaction(true)?;
+
+        let apply_ret = res?;
+        let gas_used = apply_ret.msg_receipt.gas_used;
+        Ok(GasEstimate {
+            gas_used,
+            gas_limit: max_gas_limit_without_burn(gas_used),
+            apply_ret,
+        })
+    }
+
+    /// Flush the state-tree to the underlying blockstore.
+    fn flush(&mut self) -> anyhow::Result<Cid> {
+        let k = (**self).flush()?;
+        Ok(k)
+    }
+}
+
+impl<K> DefaultExecutor<K>
+where
+    K: Kernel,
+{
+    /// Create a new [`DefaultExecutor`] for executing messages on the [`Machine`].
+    pub fn new(
+        engine_pool: EnginePool,
+        machine: <K::CallManager as CallManager>::Machine,
+    ) -> anyhow::Result<Self> {
+        // Never evict the builtin actors from the module cache.
+        engine_pool.pin_modules(machine.builtin_actors().builtin_actor_codes());
+
+        // Skip preloading all builtin actors when testing.
+        #[cfg(not(any(test, feature = "testing")))]
+        {
+            // Preload any uncached modules.
+            // This interface works for now because we know all actor CIDs
+            // ahead of time, but with user-supplied code, we won't have that
+            // guarantee.
+            engine_pool.acquire().preload(
+                machine.blockstore(),
+                machine.builtin_actors().builtin_actor_codes(),
+            )?;
+        }
+
+        Ok(Self {
+            engine_pool,
+            machine: Some(machine),
+        })
+    }
+
+    /// Consume consumes the executor and returns the Machine. If the Machine had
+    /// been poisoned during execution, the Option will be None.
+    pub fn into_machine(self) -> Option<<K::CallManager as CallManager>::Machine> {
+        self.machine
+    }
+
+    /// Applies a message, optionally skipping some of the preflight checks (when estimating).
+    fn apply_message(
+        &mut self,
+        msg: Message,
+        apply_kind: ApplyKind,
+        raw_length: usize,
+        options: &EstimateOptions,
     ) -> anyhow::Result<ApplyRet> {
         // Validate if the message was correct, charge for it, and extract some preliminary data.
         let (sender_id, gas_cost, inclusion_cost) =
-            match self.preflight_message(&msg, apply_kind, raw_length)? {
+            match self.preflight_message(&msg, apply_kind, raw_length, options)? {
                 Ok(res) => res,
                 Err(apply_ret) => return Ok(apply_ret),
             };
@@ -76,8 +154,10 @@ where
             gas_used: i64,
             backtrace: Backtrace,
             exec_trace: ExecutionTrace,
+            call_tree: CallTree,
             events_root: Option<Cid>,
             events: Vec<StampedEvent>, // TODO consider removing if nothing in the client ends up using it.
+            logs: Vec<ActorLog>,
         }
 
         // Filecoin caps the premium plus the base-fee at the fee-cap.
@@ -173,8 +253,10 @@ where
                     gas_used: res.gas_used,
                     backtrace: res.backtrace,
                     exec_trace: res.exec_trace,
+                    call_tree: res.call_tree,
                     events_root,
                     events: res.events,
+                    logs: res.logs,
                 }),
                 machine,
             )
@@ -185,8 +267,10 @@ where
             gas_used,
             mut backtrace,
             exec_trace,
+            call_tree,
             events_root,
             events,
+            logs,
         } = ret;
 
         // Extract the exit code and build the result of the message application.
@@ -273,7 +357,9 @@ where
                 failure_info,
                 gas_cost,
                 exec_trace,
+                call_tree,
                 events,
+                logs,
             ),
             ApplyKind::Implicit => Ok(ApplyRet {
                 msg_receipt: receipt,
@@ -286,51 +372,13 @@ where
                 gas_burned: 0,
                 failure_info,
                 exec_trace,
+                call_tree,
                 events,
+                logs,
             }),
         }
     }
 
-    /// Flush the state-tree to the underlying blockstore.
-    fn flush(&mut self) -> anyhow::Result<Cid> {
-        let k = (**self).flush()?;
-        Ok(k)
-    }
-}
-
-impl<K> DefaultExecutor<K>
-where
-    K: Kernel,
-{
-    /// Create a new [`DefaultExecutor`] for executing messages on the [`Machine`].
-    pub fn new(
-        engine_pool: EnginePool,
-        machine: <K::CallManager as CallManager>::Machine,
-    ) -> anyhow::Result<Self> {
-        // Skip preloading all builtin actors when testing.
-        #[cfg(not(any(test, feature = "testing")))]
-        {
-            // Preload any uncached modules.
-            // This interface works for now because we know all actor CIDs
-            // ahead of time, but with user-supplied code, we won't have that
-            // guarantee.
-            engine_pool.acquire().preload(
-                machine.blockstore(),
-                machine.builtin_actors().builtin_actor_codes(),
-            )?;
-        }
-        Ok(Self {
-            engine_pool,
-            machine: Some(machine),
-        })
-    }
-
-    /// Consume consumes the executor and returns the Machine. If the Machine had
-    /// been poisoned during execution, the Option will be None.
-    pub fn into_machine(self) -> Option<<K::CallManager as CallManager>::Machine> {
-        self.machine
-    }
-
     // TODO: The return type here is very strange because we have three cases:
     //  1. Continue: Return sender ID, & gas).
     //  2. Short-circuit: Return ApplyRet).
@@ -341,6 +389,7 @@ where
         msg: &Message,
         apply_kind: ApplyKind,
         raw_length: usize,
+        options: &EstimateOptions,
     ) -> Result<StdResult<(ActorID, TokenAmount, GasCharge), ApplyRet>> {
         msg.check().or_fatal()?;
 
@@ -435,7 +484,7 @@ where
         };
 
         // Check sequence is correct
-        if msg.sequence != sender_state.sequence {
+        if !options.skip_sequence_check && msg.sequence != sender_state.sequence {
             return Ok(Err(ApplyRet::prevalidation_fail(
                 ExitCode::SYS_SENDER_STATE_INVALID,
                 format!(
@@ -450,7 +499,11 @@ where
 
         // Ensure from actor has enough balance to cover the gas cost of the message.
         let gas_cost: TokenAmount = msg.gas_fee_cap.clone() * msg.gas_limit;
-        if sender_state.balance < gas_cost {
+        if options.skip_balance_check {
+            // Top up the sender so it can cover the gas. This is only ever done when estimating, in
+            // which case all state changes are reverted anyways.
+            sender_state.deposit_funds(&gas_cost);
+        } else if sender_state.balance < gas_cost {
             return Ok(Err(ApplyRet::prevalidation_fail(
                 ExitCode::SYS_SENDER_STATE_INVALID,
                 format!(
@@ -478,7 +531,9 @@ where
         failure_info: Option<ApplyFailure>,
         gas_cost: TokenAmount,
         exec_trace: ExecutionTrace,
+        call_tree: CallTree,
         events: Vec<StampedEvent>,
+        logs: Vec<ActorLog>,
     ) -> anyhow::Result<ApplyRet> {
         // NOTE: we don't support old network versions in the FVM, so we always burn.
         let GasOutputs {
@@ -538,7 +593,9 @@ where
             gas_burned,
             failure_info,
             exec_trace,
+            call_tree,
             events,
+            logs,
         })
     }
 
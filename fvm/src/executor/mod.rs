@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 mod default;
 mod threaded;
+mod tipset;
 
 use std::fmt::Display;
 
@@ -14,10 +15,13 @@ use fvm_shared::event::StampedEvent;
 use fvm_shared::message::Message;
 use fvm_shared::receipt::Receipt;
 use num_traits::Zero;
+use serde::{Deserialize, Serialize};
 pub use threaded::ThreadedExecutor;
+pub use tipset::{ApplyTipsetRet, BlockMessages, ChainMessage, TipsetExecutor};
 
 use crate::call_manager::Backtrace;
-use crate::trace::ExecutionTrace;
+use crate::export::human;
+use crate::trace::{ActorLog, CallTree, ExecutionTrace};
 use crate::Kernel;
 
 /// An executor executes messages on the underlying machine/kernel. It's responsible for:
@@ -42,12 +46,29 @@ pub trait Executor {
         raw_length: usize,
     ) -> anyhow::Result<ApplyRet>;
 
+    /// Estimates the gas required to execute a message without mutating the state-tree. The
+    /// message is applied as an explicit message, after which all state changes (including the
+    /// sender's nonce and balance updates) are reverted.
+    ///
+    /// The message will be executed with the supplied gas limit, so callers should set it to the
+    /// maximum they're willing to spend (e.g., the block gas limit).
+    ///
+    /// By default, estimation isn't supported and this returns an error.
+    fn estimate_message(
+        &mut self,
+        _msg: Message,
+        _raw_length: usize,
+        _options: EstimateOptions,
+    ) -> anyhow::Result<GasEstimate> {
+        anyhow::bail!("this executor doesn't support gas estimation")
+    }
+
     /// Flushes the state-tree, returning the new root CID.
     fn flush(&mut self) -> anyhow::Result<Cid>;
 }
 
 /// A description of some failure encountered when applying a message.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ApplyFailure {
     /// The backtrace from a message failure.
     MessageBacktrace(Backtrace),
@@ -71,18 +92,24 @@ impl Display for ApplyFailure {
 }
 
 /// Apply message return data.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ApplyRet {
     /// Message receipt for the transaction. This data is stored on chain.
+    #[serde(with = "human::receipt")]
     pub msg_receipt: Receipt,
     /// Gas penalty from transaction, if any.
+    #[serde(with = "human")]
     pub penalty: TokenAmount,
     /// Tip given to miner from message.
+    #[serde(with = "human")]
     pub miner_tip: TokenAmount,
 
     // Gas stuffs
+    #[serde(with = "human")]
     pub base_fee_burn: TokenAmount,
+    #[serde(with = "human")]
     pub over_estimation_burn: TokenAmount,
+    #[serde(with = "human")]
     pub refund: TokenAmount,
     pub gas_refund: i64,
     pub gas_burned: i64,
@@ -91,8 +118,15 @@ pub struct ApplyRet {
     pub failure_info: Option<ApplyFailure>,
     /// Execution trace information, for debugging.
     pub exec_trace: ExecutionTrace,
+    /// Execution trace information as a call tree, for debugging.
+    pub call_tree: CallTree,
     /// Events generated while applying the message.
     pub events: Vec<StampedEvent>,
+    /// Debug logs emitted by actors while applying the message, if
+    /// [`MachineContext::collect_actor_logs`](crate::machine::MachineContext::collect_actor_logs)
+    /// is enabled.
+    #[serde(default)]
+    pub logs: Vec<ActorLog>,
 }
 
 impl ApplyRet {
@@ -118,7 +152,9 @@ impl ApplyRet {
             gas_burned: 0,
             failure_info: Some(ApplyFailure::PreValidation(message.into())),
             exec_trace: vec![],
+            call_tree: CallTree::default(),
             events: vec![],
+            logs: vec![],
         }
     }
 }
@@ -134,3 +170,24 @@ pub enum ApplyKind {
     Explicit,
     Implicit,
 }
+
+/// Options for [`Executor::estimate_message`]. By default, all preflight checks are performed.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub struct EstimateOptions {
+    /// Don't check the message's sequence (nonce) against the sender's sequence.
+    pub skip_sequence_check: bool,
+    /// Don't check that the sender's balance covers the gas fee cap times the gas limit.
+    pub skip_balance_check: bool,
+}
+
+/// The result of a gas estimation.
+#[derive(Clone, Debug)]
+pub struct GasEstimate {
+    /// The gas used by the message.
+    pub gas_used: i64,
+    /// The recommended gas limit. This is the largest gas limit that won't incur any
+    /// over-estimation burn given the gas used.
+    pub gas_limit: i64,
+    /// The result of applying the message. All state changes have already been reverted.
+    pub apply_ret: ApplyRet,
+}
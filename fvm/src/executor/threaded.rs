@@ -5,7 +5,7 @@ use cid::Cid;
 use fvm_shared::message::Message;
 use lazy_static::lazy_static;
 
-use super::{ApplyKind, ApplyRet, Executor};
+use super::{ApplyKind, ApplyRet, EstimateOptions, Executor, GasEstimate};
 
 lazy_static! {
     static ref EXEC_POOL: yastl::Pool = yastl::Pool::with_config(
@@ -47,6 +47,21 @@ where
         ret
     }
 
+    fn estimate_message(
+        &mut self,
+        msg: Message,
+        raw_length: usize,
+        options: EstimateOptions,
+    ) -> anyhow::Result<GasEstimate> {
+        let mut ret = Err(anyhow!("failed to estimate"));
+
+        EXEC_POOL.scoped(|scope| {
+            scope.execute(|| ret = self.0.estimate_message(msg, raw_length, options));
+        });
+
+        ret
+    }
+
     fn flush(&mut self) -> anyhow::Result<Cid> {
         self.0.flush()
     }
@@ -0,0 +1,252 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::collections::HashSet;
+use std::ops::{Deref, DerefMut};
+
+use anyhow::{anyhow, Context as _};
+use cid::Cid;
+use fvm_ipld_amt::Amtv0;
+use fvm_ipld_blockstore::Buffered;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::message::Message;
+use fvm_shared::{ActorID, MethodNum, BLOCK_GAS_LIMIT};
+use num_traits::Zero;
+
+use super::{ApplyKind, ApplyRet, DefaultExecutor, Executor};
+use crate::call_manager::CallManager;
+use crate::machine::{Machine, CRON_ACTOR_ID, REWARD_ACTOR_ID};
+use crate::system_actor::SYSTEM_ACTOR_ID;
+use crate::Kernel;
+
+/// The reward actor's `AwardBlockReward` method.
+const AWARD_BLOCK_REWARD_METHOD: MethodNum = 2;
+/// The cron actor's `EpochTick` method.
+const EPOCH_TICK_METHOD: MethodNum = 2;
+
+/// The gas limit of the implicit block reward messages.
+const REWARD_GAS_LIMIT: i64 = 1 << 30;
+/// The gas limit of the implicit cron message.
+const CRON_GAS_LIMIT: i64 = BLOCK_GAS_LIMIT * 10000;
+
+/// A message included in a block.
+#[derive(Clone, Debug)]
+pub struct ChainMessage {
+    /// The CID of the message as included on-chain (i.e., the CID of the _signed_ message for
+    /// secp256k1 messages). Messages are deduplicated across the tipset by this CID.
+    pub cid: Cid,
+    /// The message to apply.
+    pub message: Message,
+    /// The length of the message as it appears on-chain, used to charge message inclusion gas.
+    pub raw_length: usize,
+}
+
+/// The messages included in a single block, along with the information needed to reward the
+/// block's miner.
+#[derive(Clone, Debug)]
+pub struct BlockMessages {
+    /// The miner that produced the block.
+    pub miner: Address,
+    /// The number of winning tickets in the block's election proof.
+    pub win_count: i64,
+    /// The block's messages, in the order in which they should be applied.
+    pub messages: Vec<ChainMessage>,
+}
+
+/// The result of applying a tipset.
+#[derive(Clone, Debug)]
+pub struct ApplyTipsetRet {
+    /// The new state root.
+    pub state_root: Cid,
+    /// The root of the receipts AMT (one receipt per applied message).
+    pub receipts_root: Cid,
+    /// The events root of each applied message, in the same order as the receipts.
+    pub events_roots: Vec<Option<Cid>>,
+    /// The CID and result of each applied message, in the same order as the receipts.
+    pub message_rets: Vec<(Cid, ApplyRet)>,
+    /// The result of each block's implicit reward message.
+    pub reward_rets: Vec<ApplyRet>,
+    /// The result of the implicit cron message.
+    pub cron_ret: ApplyRet,
+}
+
+/// Parameters for the reward actor's `AwardBlockReward` method.
+#[derive(Serialize_tuple)]
+struct AwardBlockRewardParams {
+    miner: Address,
+    penalty: TokenAmount,
+    gas_reward: TokenAmount,
+    win_count: i64,
+}
+
+/// An executor for applying entire tipsets on top of a [`DefaultExecutor`]. For each block, it
+/// applies the block's (deduplicated) messages followed by the block reward. It then runs cron,
+/// flushes the state-tree, and commits the receipts.
+///
+/// The underlying machine is bound to a single epoch, so null rounds (and their cron invocations)
+/// must be handled by the caller with a machine per epoch.
+///
+/// # Warning
+///
+/// Like the [`DefaultExecutor`], this executor might run out of stack and crash the entire process
+/// if it doesn't have at least 64MiB of stack space.
+pub struct TipsetExecutor<K: Kernel> {
+    executor: DefaultExecutor<K>,
+}
+
+impl<K: Kernel> Deref for TipsetExecutor<K> {
+    type Target = DefaultExecutor<K>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.executor
+    }
+}
+
+impl<K: Kernel> DerefMut for TipsetExecutor<K> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.executor
+    }
+}
+
+impl<K> TipsetExecutor<K>
+where
+    K: Kernel,
+    <<K::CallManager as CallManager>::Machine as Machine>::Blockstore: Buffered,
+{
+    /// Create a new [`TipsetExecutor`] wrapping the given executor.
+    pub fn new(executor: DefaultExecutor<K>) -> Self {
+        Self { executor }
+    }
+
+    /// Consumes the tipset executor and returns the inner [`DefaultExecutor`].
+    pub fn into_inner(self) -> DefaultExecutor<K> {
+        self.executor
+    }
+
+    /// Applies a tipset given the messages of each of its blocks, in block order.
+    pub fn apply_tipset(&mut self, blocks: Vec<BlockMessages>) -> anyhow::Result<ApplyTipsetRet> {
+        let mut seen = HashSet::new();
+        let mut message_rets = Vec::new();
+        let mut reward_rets = Vec::with_capacity(blocks.len());
+
+        for block in blocks {
+            let mut penalty = TokenAmount::zero();
+            let mut gas_reward = TokenAmount::zero();
+
+            for ChainMessage {
+                cid,
+                message,
+                raw_length,
+            } in block.messages
+            {
+                // Messages may be included in multiple blocks, but are only applied once.
+                if !seen.insert(cid) {
+                    continue;
+                }
+
+                let ret = self
+                    .executor
+                    .execute_message(message, ApplyKind::Explicit, raw_length)
+                    .with_context(|| format!("failed to apply message {}", cid))?;
+
+                penalty += &ret.penalty;
+                gas_reward += &ret.miner_tip;
+                message_rets.push((cid, ret));
+            }
+
+            let params = RawBytes::serialize(AwardBlockRewardParams {
+                miner: block.miner,
+                penalty,
+                gas_reward,
+                win_count: block.win_count,
+            })?;
+            let ret = self
+                .apply_implicit(
+                    REWARD_ACTOR_ID,
+                    AWARD_BLOCK_REWARD_METHOD,
+                    params,
+                    REWARD_GAS_LIMIT,
+                )
+                .with_context(|| format!("failed to reward miner {}", block.miner))?;
+            reward_rets.push(ret);
+        }
+
+        let cron_ret = self
+            .apply_implicit(
+                CRON_ACTOR_ID,
+                EPOCH_TICK_METHOD,
+                RawBytes::default(),
+                CRON_GAS_LIMIT,
+            )
+            .context("failed to run cron")?;
+
+        let state_root = self.executor.flush()?;
+
+        let receipts_root = {
+            let blockstore = self.executor.blockstore();
+            let root = Amtv0::new_from_iter(
+                blockstore,
+                message_rets.iter().map(|(_, ret)| &ret.msg_receipt),
+            )
+            .context("failed to build the receipts AMT")?;
+            blockstore
+                .flush(&root)
+                .context("failed to flush the receipts AMT")?;
+            root
+        };
+
+        let events_roots = message_rets
+            .iter()
+            .map(|(_, ret)| ret.msg_receipt.events_root)
+            .collect();
+
+        Ok(ApplyTipsetRet {
+            state_root,
+            receipts_root,
+            events_roots,
+            message_rets,
+            reward_rets,
+            cron_ret,
+        })
+    }
+
+    /// Applies an implicit message from the system actor, failing if the message fails.
+    fn apply_implicit(
+        &mut self,
+        to: ActorID,
+        method_num: MethodNum,
+        params: RawBytes,
+        gas_limit: i64,
+    ) -> anyhow::Result<ApplyRet> {
+        let msg = Message {
+            version: 0,
+            from: Address::new_id(SYSTEM_ACTOR_ID),
+            to: Address::new_id(to),
+            sequence: self.executor.context().epoch as u64,
+            value: TokenAmount::zero(),
+            method_num,
+            params,
+            gas_limit,
+            gas_fee_cap: TokenAmount::zero(),
+            gas_premium: TokenAmount::zero(),
+        };
+
+        let ret = self.executor.execute_message(msg, ApplyKind::Implicit, 0)?;
+        if !ret.msg_receipt.exit_code.is_success() {
+            return Err(match &ret.failure_info {
+                Some(info) => anyhow!(
+                    "implicit message failed with exit code {}: {}",
+                    ret.msg_receipt.exit_code,
+                    info
+                ),
+                None => anyhow!(
+                    "implicit message failed with exit code {}",
+                    ret.msg_receipt.exit_code
+                ),
+            });
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,586 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+//! Stable, versioned serialization of execution results and traces ([`ApplyRet`],
+//! [`ExecutionEvent`], [`CallTree`], [`Backtrace`], etc.), as JSON for humans and DAG-CBOR for
+//! storage.
+//!
+//! Exported values are wrapped in an envelope recording the [`FORMAT_VERSION`] they were written
+//! with. The version is only bumped on incompatible changes: fields may be added to the format
+//! without bumping it, and unknown fields are ignored when loading newer files. Fields added after
+//! the first version (e.g., [`ApplyRet::logs`]) must be marked `#[serde(default)]`, so that older
+//! files still load. Execution events added by newer versions are loaded as
+//! [`ExecutionEvent::Unknown`].
+//!
+//! In JSON, CIDs, addresses and token amounts are written as strings, and raw bytes are base64
+//! encoded. In DAG-CBOR, they use their usual binary encoding.
+//!
+//! [`ApplyRet`]: crate::executor::ApplyRet
+//! [`ApplyRet::logs`]: crate::executor::ApplyRet::logs
+//! [`ExecutionEvent`]: crate::trace::ExecutionEvent
+//! [`ExecutionEvent::Unknown`]: crate::trace::ExecutionEvent::Unknown
+//! [`CallTree`]: crate::trace::CallTree
+//! [`Backtrace`]: crate::call_manager::Backtrace
+use anyhow::{anyhow, Context as _};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// The current version of the export format.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct Payload<T> {
+    data: T,
+}
+
+fn check_version(Header { version }: Header) -> anyhow::Result<()> {
+    if version > FORMAT_VERSION {
+        return Err(anyhow!(
+            "unsupported export format version {} (expected at most {})",
+            version,
+            FORMAT_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Exports a value as (pretty-printed) JSON.
+pub fn to_json<T: Serialize>(value: &T) -> anyhow::Result<String> {
+    serde_json::to_string_pretty(&Envelope {
+        version: FORMAT_VERSION,
+        data: value,
+    })
+    .context("failed to export value as JSON")
+}
+
+/// Loads a value exported with [`to_json`].
+pub fn from_json<T: DeserializeOwned>(json: &str) -> anyhow::Result<T> {
+    check_version(serde_json::from_str(json).context("failed to read export format version")?)?;
+    let Payload { data } = serde_json::from_str(json).context("failed to load exported JSON")?;
+    Ok(data)
+}
+
+/// Exports a value as DAG-CBOR.
+pub fn to_cbor<T: Serialize>(value: &T) -> anyhow::Result<Vec<u8>> {
+    fvm_ipld_encoding::to_vec(&Envelope {
+        version: FORMAT_VERSION,
+        data: value,
+    })
+    .context("failed to export value as CBOR")
+}
+
+/// Loads a value exported with [`to_cbor`].
+pub fn from_cbor<T: DeserializeOwned>(cbor: &[u8]) -> anyhow::Result<T> {
+    check_version(
+        fvm_ipld_encoding::from_slice(cbor).context("failed to read export format version")?,
+    )?;
+    let Payload { data } =
+        fvm_ipld_encoding::from_slice(cbor).context("failed to load exported CBOR")?;
+    Ok(data)
+}
+
+/// Deserialization of adjacently tagged enums that may gain variants in newer versions of the
+/// format.
+pub(crate) mod tagged {
+    use std::cell::Cell;
+    use std::marker::PhantomData;
+
+    use serde::de::value::{StrDeserializer, StringDeserializer};
+    use serde::de::{DeserializeSeed, Error as _, IgnoredAny, MapAccess, Visitor};
+    use serde::{forward_to_deserialize_any, Deserializer};
+
+    /// An adjacently tagged enum, serialized with its tag first.
+    pub trait TaggedEnum: Sized {
+        /// The name of the tag field.
+        const TAG: &'static str;
+
+        /// The variant to use for unknown tags.
+        fn unknown() -> Self;
+
+        /// Deserializes a known variant (usually with a derived, `remote = "Self"` implementation).
+        fn deserialize_known<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+    }
+
+    /// Deserializes a [`TaggedEnum`], skipping the content of variants with unknown tags.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: TaggedEnum,
+        D: Deserializer<'de>,
+    {
+        let human_readable = deserializer.is_human_readable();
+        deserializer.deserialize_map(TaggedVisitor {
+            human_readable,
+            _marker: PhantomData,
+        })
+    }
+
+    struct TaggedVisitor<T> {
+        human_readable: bool,
+        _marker: PhantomData<T>,
+    }
+
+    impl<'de, T: TaggedEnum> Visitor<'de> for TaggedVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "a map starting with a {:?} field", T::TAG)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<T, A::Error> {
+            if map.next_key::<String>()?.as_deref() != Some(T::TAG) {
+                return Err(A::Error::custom(format!(
+                    "expected a {:?} field first",
+                    T::TAG
+                )));
+            }
+            let tag: String = map.next_value()?;
+
+            // Replay the tag to the derived implementation, noting whether it rejects it. If it
+            // does, nothing but the tag has been consumed, so we can skip the rest.
+            let unknown = Cell::new(false);
+            let res = T::deserialize_known(Replay {
+                map: &mut map,
+                tag_key: Some(T::TAG),
+                tag: Some(tag),
+                unknown: &unknown,
+                human_readable: self.human_readable,
+            });
+            match res {
+                Err(_) if unknown.get() => {
+                    while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+                    Ok(T::unknown())
+                }
+                res => res,
+            }
+        }
+    }
+
+    /// A deserializer (and map) that yields the already consumed tag, followed by the remaining
+    /// entries of the underlying map.
+    struct Replay<'a, A> {
+        map: &'a mut A,
+        tag_key: Option<&'static str>,
+        tag: Option<String>,
+        unknown: &'a Cell<bool>,
+        human_readable: bool,
+    }
+
+    impl<'de, 'a, A: MapAccess<'de>> Deserializer<'de> for Replay<'a, A> {
+        type Error = A::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, A::Error> {
+            visitor.visit_map(self)
+        }
+
+        fn is_human_readable(&self) -> bool {
+            self.human_readable
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct enum identifier ignored_any
+        }
+    }
+
+    impl<'de, 'a, A: MapAccess<'de>> MapAccess<'de> for Replay<'a, A> {
+        type Error = A::Error;
+
+        fn next_key_seed<K: DeserializeSeed<'de>>(
+            &mut self,
+            seed: K,
+        ) -> Result<Option<K::Value>, A::Error> {
+            match self.tag_key.take() {
+                Some(key) => seed.deserialize(StrDeserializer::new(key)).map(Some),
+                None => self.map.next_key_seed(seed),
+            }
+        }
+
+        fn next_value_seed<V: DeserializeSeed<'de>>(
+            &mut self,
+            seed: V,
+        ) -> Result<V::Value, A::Error> {
+            match self.tag.take() {
+                Some(tag) => {
+                    let res = seed.deserialize(StringDeserializer::new(tag));
+                    self.unknown.set(res.is_err());
+                    res
+                }
+                None => self.map.next_value_seed(seed),
+            }
+        }
+    }
+}
+
+/// Serde helpers for fields that should be written as strings in human-readable formats, for use
+/// with `#[serde(with = "...")]`.
+pub(crate) mod human {
+    use std::str::FromStr;
+
+    use cid::Cid;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::{Address, Network};
+    use fvm_shared::bigint::BigInt;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::{ErrorNumber, ExitCode};
+    use fvm_shared::receipt::Receipt;
+    use num_traits::FromPrimitive;
+    use serde::de::{DeserializeOwned, Error as _};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// A type with a string representation for human-readable formats.
+    pub trait HumanReadable: Serialize + DeserializeOwned {
+        fn to_human(&self) -> String;
+        fn from_human(s: &str) -> anyhow::Result<Self>;
+    }
+
+    impl HumanReadable for Cid {
+        fn to_human(&self) -> String {
+            self.to_string()
+        }
+
+        fn from_human(s: &str) -> anyhow::Result<Self> {
+            Ok(Cid::from_str(s)?)
+        }
+    }
+
+    impl HumanReadable for Address {
+        fn to_human(&self) -> String {
+            self.to_string()
+        }
+
+        // Accept addresses from either network, as the file may come from another network.
+        fn from_human(s: &str) -> anyhow::Result<Self> {
+            Ok(Network::Mainnet
+                .parse_address(s)
+                .or_else(|_| Network::Testnet.parse_address(s))?)
+        }
+    }
+
+    impl HumanReadable for TokenAmount {
+        fn to_human(&self) -> String {
+            self.atto().to_string()
+        }
+
+        fn from_human(s: &str) -> anyhow::Result<Self> {
+            Ok(TokenAmount::from_atto(BigInt::from_str(s)?))
+        }
+    }
+
+    impl HumanReadable for RawBytes {
+        fn to_human(&self) -> String {
+            base64::encode(self.bytes())
+        }
+
+        fn from_human(s: &str) -> anyhow::Result<Self> {
+            Ok(RawBytes::new(base64::decode(s)?))
+        }
+    }
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: HumanReadable,
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&value.to_human())
+        } else {
+            value.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: HumanReadable,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            T::from_human(&s).map_err(D::Error::custom)
+        } else {
+            T::deserialize(deserializer)
+        }
+    }
+
+    /// Wraps a value so it's deserialized with [`deserialize`].
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct Human<T: HumanReadable>(#[serde(deserialize_with = "deserialize")] T);
+
+    /// Wraps a reference so it's serialized with [`serialize`].
+    struct HumanRef<'a, T>(&'a T);
+
+    impl<T: HumanReadable> Serialize for HumanRef<'_, T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize(self.0, serializer)
+        }
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: HumanReadable,
+            S: Serializer,
+        {
+            value.as_ref().map(HumanRef).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            T: HumanReadable,
+            D: Deserializer<'de>,
+        {
+            Ok(Option::<Human<T>>::deserialize(deserializer)?.map(|h| h.0))
+        }
+    }
+
+    pub mod vec {
+        use super::*;
+
+        pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: HumanReadable,
+            S: Serializer,
+        {
+            serializer.collect_seq(values.iter().map(HumanRef))
+        }
+
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+        where
+            T: HumanReadable,
+            D: Deserializer<'de>,
+        {
+            Ok(Vec::<Human<T>>::deserialize(deserializer)?
+                .into_iter()
+                .map(|h| h.0)
+                .collect())
+        }
+    }
+
+    /// (De)serializes a [`Receipt`] as a map, with human-readable fields.
+    pub mod receipt {
+        use super::*;
+
+        #[derive(Serialize, Deserialize)]
+        struct ReceiptRepr {
+            exit_code: ExitCode,
+            #[serde(with = "super")]
+            return_data: RawBytes,
+            gas_used: i64,
+            #[serde(with = "super::option")]
+            events_root: Option<Cid>,
+        }
+
+        pub fn serialize<S: Serializer>(value: &Receipt, serializer: S) -> Result<S::Ok, S::Error> {
+            ReceiptRepr {
+                exit_code: value.exit_code,
+                return_data: value.return_data.clone(),
+                gas_used: value.gas_used,
+                events_root: value.events_root,
+            }
+            .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Receipt, D::Error> {
+            let ReceiptRepr {
+                exit_code,
+                return_data,
+                gas_used,
+                events_root,
+            } = ReceiptRepr::deserialize(deserializer)?;
+            Ok(Receipt {
+                exit_code,
+                return_data,
+                gas_used,
+                events_root,
+            })
+        }
+    }
+
+    /// (De)serializes an [`ErrorNumber`] as its numeric value.
+    pub mod error_number {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            value: &ErrorNumber,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u32(*value as u32)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<ErrorNumber, D::Error> {
+            let value = u32::deserialize(deserializer)?;
+            ErrorNumber::from_u32(value)
+                .ok_or_else(|| D::Error::custom(format!("unknown error number {}", value)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::{ErrorNumber, ExitCode};
+    use fvm_shared::receipt::Receipt;
+
+    use super::*;
+    use crate::call_manager::backtrace::{Cause, Frame};
+    use crate::call_manager::Backtrace;
+    use crate::executor::{ApplyFailure, ApplyRet};
+    use crate::gas::{Gas, GasCharge};
+    use crate::kernel::SyscallError;
+    use crate::trace::{ActorLog, CallTree, ExecutionEvent};
+    use crate::EMPTY_ARR_CID;
+
+    fn apply_ret() -> ApplyRet {
+        let exec_trace = vec![
+            ExecutionEvent::GasCharge(GasCharge::new("OnChainMessage", Gas::new(10), Gas::new(5))),
+            ExecutionEvent::Call {
+                from: 100,
+                to: Address::new_id(1000),
+                method: 2,
+                params: RawBytes::new(vec![1, 2, 3]),
+                value: TokenAmount::from_atto(42),
+            },
+            ExecutionEvent::BlockOpen {
+                actor: 1000,
+                cid: *EMPTY_ARR_CID,
+                codec: fvm_ipld_encoding::DAG_CBOR,
+                size: 1,
+            },
+            ExecutionEvent::CallError(SyscallError::new(ErrorNumber::NotFound, "not found")),
+        ];
+        let mut backtrace = Backtrace::default();
+        backtrace.begin(Cause::from_syscall(
+            "send",
+            "send",
+            SyscallError::new(ErrorNumber::NotFound, "not found"),
+        ));
+        backtrace.push_frame(Frame {
+            source: 1000,
+            method: 2,
+            code: ExitCode::USR_NOT_FOUND,
+            message: "oops".into(),
+        });
+        ApplyRet {
+            msg_receipt: Receipt {
+                exit_code: ExitCode::USR_NOT_FOUND,
+                return_data: RawBytes::new(vec![4, 5]),
+                gas_used: 1234,
+                events_root: Some(*EMPTY_ARR_CID),
+            },
+            penalty: TokenAmount::from_atto(1),
+            miner_tip: TokenAmount::from_atto(2),
+            base_fee_burn: TokenAmount::from_atto(3),
+            over_estimation_burn: TokenAmount::from_atto(4),
+            refund: TokenAmount::from_atto(5),
+            gas_refund: 6,
+            gas_burned: 7,
+            failure_info: Some(ApplyFailure::MessageBacktrace(backtrace)),
+            call_tree: CallTree::from_flat(&exec_trace).unwrap(),
+            exec_trace,
+            events: vec![],
+            logs: vec![ActorLog {
+                actor: 1000,
+                method: 2,
+                invocation: 1,
+                nonce: 3,
+                msg: "hello".into(),
+            }],
+        }
+    }
+
+    // The types don't implement `PartialEq`, so we compare their debug representations.
+    fn assert_same(a: &ApplyRet, b: &ApplyRet) {
+        assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let ret = apply_ret();
+        let json = to_json(&ret).unwrap();
+        assert_same(&ret, &from_json(&json).unwrap());
+
+        // CIDs, addresses, and amounts are human readable.
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], FORMAT_VERSION);
+        let data = &value["data"];
+        assert_eq!(data["penalty"], "1");
+        assert_eq!(
+            data["msg_receipt"]["events_root"],
+            EMPTY_ARR_CID.to_string()
+        );
+        assert_eq!(data["msg_receipt"]["return_data"], "BAU=");
+        assert_eq!(data["exec_trace"][1]["type"], "Call");
+        assert_eq!(data["exec_trace"][1]["data"]["to"], "f01000");
+        assert_eq!(
+            data["call_tree"]["calls"][0]["ipld_reads"][0],
+            EMPTY_ARR_CID.to_string()
+        );
+    }
+
+    #[test]
+    fn cbor_roundtrip() {
+        let ret = apply_ret();
+        let cbor = to_cbor(&ret).unwrap();
+        assert_same(&ret, &from_cbor(&cbor).unwrap());
+    }
+
+    #[test]
+    fn compatibility() {
+        // Unknown fields and events are tolerated.
+        let json = r#"{
+            "version": 1,
+            "extra": true,
+            "data": [
+                {"type": "GasCharge", "data": {"name": "foo", "compute_gas": 1000, "other_gas": 0, "new": 1}},
+                {"type": "FutureEvent", "data": {"cid": "bafy"}}
+            ]
+        }"#;
+        let trace: Vec<ExecutionEvent> = from_json(json).unwrap();
+        assert!(matches!(&trace[0], ExecutionEvent::GasCharge(c) if c.compute_gas == Gas::new(1)));
+        assert!(matches!(trace[1], ExecutionEvent::Unknown));
+
+        // Newer, incompatible versions are rejected.
+        let json = r#"{"version": 2, "data": []}"#;
+        assert!(from_json::<Vec<ExecutionEvent>>(json).is_err());
+        let cbor = fvm_ipld_encoding::to_vec(&Envelope {
+            version: FORMAT_VERSION + 1,
+            data: &Vec::<Cid>::new(),
+        })
+        .unwrap();
+        assert!(from_cbor::<Vec<Cid>>(&cbor).is_err());
+    }
+
+    #[test]
+    fn legacy_fields() {
+        // Exports from before fields were added to the first version load, with the new fields
+        // defaulted.
+        let mut ret = apply_ret();
+        let mut legacy: serde_json::Value = serde_json::from_str(&to_json(&ret).unwrap()).unwrap();
+        let data = legacy["data"].as_object_mut().unwrap();
+        assert!(data.remove("logs").is_some());
+        let json = serde_json::to_string(&legacy).unwrap();
+        let loaded: ApplyRet = from_json(&json).unwrap();
+        ret.logs.clear();
+        assert_same(&ret, &loaded);
+    }
+}
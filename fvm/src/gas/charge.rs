@@ -4,12 +4,14 @@
 
 use std::borrow::Cow;
 
+use serde::{Deserialize, Serialize};
+
 use super::timer::GasDuration;
 use super::Gas;
 
 /// Single gas charge in the VM. Contains information about what gas was for, as well
 /// as the amount of gas needed for computation and storage respectively.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GasCharge {
     pub name: Cow<'static, str>,
     /// Gas charged for immediate computation.
@@ -26,6 +28,7 @@ pub struct GasCharge {
     pub other_gas: Gas,
 
     /// Execution time related to this charge, if traced and successfully measured.
+    #[serde(skip)]
     pub elapsed: GasDuration,
 }
 
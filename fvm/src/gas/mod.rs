@@ -5,13 +5,18 @@
 use std::cell::{Cell, RefCell};
 use std::fmt::{Debug, Display};
 use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+use std::sync::Arc;
 
 use num_traits::Zero;
+use serde::{Deserialize, Serialize};
 
 pub use self::charge::GasCharge;
-pub(crate) use self::outputs::GasOutputs;
-pub use self::price_list::{price_list_by_network_version, PriceList, WasmGasPrices};
+pub(crate) use self::outputs::{max_gas_limit_without_burn, GasOutputs};
+pub use self::price_list::{
+    price_list_by_network_version, PriceList, PriceListFormat, WasmGasPrices,
+};
 pub use self::timer::{GasInstant, GasTimer};
+use crate::call_manager::ExecutionObserver;
 use crate::kernel::{ExecutionError, Result};
 
 mod charge;
@@ -27,7 +32,8 @@ pub const MILLIGAS_PRECISION: i64 = 1000;
 /// - Enforces correct units by making it impossible to, e.g., get gas squared (by multiplying gas
 ///   by gas).
 /// - Makes it harder to confuse gas and milligas.
-#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Default)]
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Gas(i64 /* milligas */);
 
 impl Debug for Gas {
@@ -185,6 +191,7 @@ pub struct GasTracker {
     gas_limit: Gas,
     gas_used: Cell<Gas>,
     trace: Option<RefCell<Vec<GasCharge>>>,
+    observer: Option<Arc<dyn ExecutionObserver>>,
 }
 
 impl GasTracker {
@@ -195,9 +202,17 @@ impl GasTracker {
             gas_limit,
             gas_used: Cell::new(gas_used),
             trace: enable_tracing.then_some(Default::default()),
+            observer: None,
         }
     }
 
+    /// Notify the given observer (if any) of all gas charges. Child gas-trackers inherit the
+    /// observer.
+    pub fn with_observer(mut self, observer: Option<Arc<dyn ExecutionObserver>>) -> Self {
+        self.observer = observer;
+        self
+    }
+
     fn charge_gas_inner(&self, to_use: Gas) -> Result<()> {
         // The gas type uses saturating math.
         let gas_used = self.gas_used.get() + to_use;
@@ -216,21 +231,25 @@ impl GasTracker {
     pub fn charge_gas(&self, name: &str, to_use: Gas) -> Result<GasTimer> {
         log::trace!("charging gas: {} {}", name, to_use);
         let res = self.charge_gas_inner(to_use);
-        if let Some(trace) = &self.trace {
-            let mut charge = GasCharge::new(name.to_owned(), to_use, Gas::zero());
-            let timer = GasTimer::new(&mut charge.elapsed);
-            trace.borrow_mut().push(charge);
-            res.map(|_| timer)
-        } else {
-            res.map(|_| GasTimer::empty())
+        if self.trace.is_none() && self.observer.is_none() {
+            return res.map(|_| GasTimer::empty());
         }
+        self.record_charge(res, GasCharge::new(name.to_owned(), to_use, Gas::zero()))
     }
 
     /// Applies the specified gas charge, where quantities are supplied in milligas.
-    pub fn apply_charge(&self, mut charge: GasCharge) -> Result<GasTimer> {
+    pub fn apply_charge(&self, charge: GasCharge) -> Result<GasTimer> {
         let to_use = charge.total();
         log::trace!("charging gas: {} {}", &charge.name, to_use);
         let res = self.charge_gas_inner(to_use);
+        self.record_charge(res, charge)
+    }
+
+    /// Notifies the observer of the charge and records it in the trace, if enabled.
+    fn record_charge(&self, res: Result<()>, mut charge: GasCharge) -> Result<GasTimer> {
+        if let Some(observer) = &self.observer {
+            observer.on_gas_charge(&charge);
+        }
         if let Some(trace) = &self.trace {
             let timer = GasTimer::new(&mut charge.elapsed);
             trace.borrow_mut().push(charge);
@@ -252,8 +271,10 @@ impl GasTracker {
     /// Make a "child" gas-tracker with a new limit, if and only if the new limit is less than the
     /// available gas.
     pub fn new_child(&self, new_limit: Gas) -> Option<GasTracker> {
-        (self.gas_available() > new_limit)
-            .then(|| GasTracker::new(new_limit, Gas::zero(), self.trace.is_some()))
+        (self.gas_available() > new_limit).then(|| {
+            GasTracker::new(new_limit, Gas::zero(), self.trace.is_some())
+                .with_observer(self.observer.clone())
+        })
     }
 
     /// Getter for the maximum gas usable by this message.
@@ -312,6 +333,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn gas_tracker_observer() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
+
+        impl ExecutionObserver for Recorder {
+            fn on_gas_charge(&self, charge: &GasCharge) {
+                self.0.lock().unwrap().push(charge.name.to_string());
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let t =
+            GasTracker::new(Gas::new(20), Gas::zero(), false).with_observer(Some(recorder.clone()));
+        let _ = t.charge_gas("a", Gas::new(5)).unwrap();
+        let child = t.new_child(Gas::new(5)).unwrap();
+        let _ = child
+            .apply_charge(GasCharge::new("b", Gas::new(1), Gas::zero()))
+            .unwrap();
+        // Charges are observed even when they exceed the limit.
+        assert!(t.charge_gas("c", Gas::new(100)).is_err());
+        assert_eq!(*recorder.0.lock().unwrap(), ["a", "b", "c"]);
+    }
+
     #[test]
     fn milligas_to_gas_round() {
         assert_eq!(milligas_to_gas(100, false), 0);
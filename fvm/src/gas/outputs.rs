@@ -61,15 +61,21 @@ impl GasOutputs {
     }
 }
 
-fn compute_gas_overestimation_burn(gas_used: i64, gas_limit: i64) -> (i64, i64) {
-    const GAS_OVERUSE_NUM: i64 = 11;
-    const GAS_OVERUSE_DENOM: i64 = 10;
+const GAS_OVERUSE_NUM: i64 = 11;
+const GAS_OVERUSE_DENOM: i64 = 10;
+
+/// Returns the gas limit (110% of the gas used) up to which a message using `gas_used` gas is exempt
+/// from the over-estimation burn.
+pub(crate) fn max_gas_limit_without_burn(gas_used: i64) -> i64 {
+    (GAS_OVERUSE_NUM * gas_used) / GAS_OVERUSE_DENOM
+}
 
+fn compute_gas_overestimation_burn(gas_used: i64, gas_limit: i64) -> (i64, i64) {
     if gas_used == 0 {
         return (0, gas_limit);
     }
 
-    let mut over = gas_limit - (GAS_OVERUSE_NUM * gas_used) / GAS_OVERUSE_DENOM;
+    let mut over = gas_limit - max_gas_limit_without_burn(gas_used);
     if over < 0 {
         return (gas_limit - gas_used, 0);
     }
@@ -85,3 +91,18 @@ fn compute_gas_overestimation_burn(gas_used: i64, gas_limit: i64) -> (i64, i64)
     let gas_to_burn = i64::try_from(gas_to_burn).unwrap();
     (gas_limit - gas_used - gas_to_burn, gas_to_burn)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_gas_limit_without_burn() {
+        for gas_used in [1, 9, 10, 1234, 1_000_000, 7_654_321] {
+            let limit = super::max_gas_limit_without_burn(gas_used);
+            assert!(limit >= gas_used);
+            assert_eq!(compute_gas_overestimation_burn(gas_used, limit).1, 0);
+            assert_ne!(compute_gas_overestimation_burn(gas_used, 2 * limit).1, 0);
+        }
+    }
+}
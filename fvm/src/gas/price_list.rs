@@ -4,8 +4,10 @@
 
 use std::collections::HashMap;
 use std::ops::Mul;
+use std::path::Path;
+use std::sync::Arc;
 
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
 use fvm_shared::crypto::signature::SignatureType;
 use fvm_shared::econ::TokenAmount;
 use fvm_shared::event::{ActorEvent, Flags};
@@ -18,7 +20,8 @@ use fvm_shared::version::NetworkVersion;
 use fvm_shared::{MethodNum, METHOD_SEND};
 use fvm_wasm_instrument::gas_metering::{InstructionCost, Operator, Rules};
 use lazy_static::lazy_static;
-use num_traits::Zero;
+use num_traits::{FromPrimitive, Zero};
+use serde::{Deserialize, Serialize};
 
 use super::GasCharge;
 use crate::gas::Gas;
@@ -65,7 +68,7 @@ macro_rules! total_enum_map {
 }
 
 lazy_static! {
-    static ref HYGGE_PRICES: PriceList = PriceList {
+    static ref HYGGE_PRICES: Arc<PriceList> = Arc::new(PriceList {
         on_chain_message_compute: ScalingCost::fixed(Gas::new(38863)),
         on_chain_message_storage: ScalingCost {
             flat: Gas::new(36),
@@ -286,10 +289,13 @@ lazy_static! {
         event_entry_index_cost: Zero::zero(),
         // TODO(#1279)
         event_per_byte_cost: Zero::zero(),
-    };
+        // Validation makes a single, allocation-free pass over each value.
+        event_validation_per_byte_cost: Gas::new(1),
+    });
 }
 
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct ScalingCost {
     pub flat: Gas,
     pub scale: Gas,
@@ -321,10 +327,12 @@ impl ScalingCost {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub(crate) struct StepCost(Vec<Step>);
 
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct Step {
     start: i64,
     cost: Gas,
@@ -349,7 +357,13 @@ impl StepCost {
 
 /// Provides prices for operations in the VM.
 /// All costs are in milligas.
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// Price lists can be serialized and loaded from files (see [`PriceList::load`]) to experiment with
+/// different prices on local networks. Maps keyed by signature types, hash functions, or proof
+/// types are keyed by the (decimal) signature type number, multicodec, or registered proof number
+/// respectively.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PriceList {
     /// Gas cost charged to the originator of an on-chain message (regardless of
     /// whether it succeeds or fails in application) is given by:
@@ -398,18 +412,23 @@ pub struct PriceList {
     pub(crate) delete_actor: Gas,
 
     /// Gas cost for verifying a cryptographic signature.
+    #[serde(with = "coded_map")]
     pub(crate) sig_cost: HashMap<SignatureType, ScalingCost>,
 
     /// Gas cost for recovering secp256k1 signer public key
     pub(crate) secp256k1_recover_cost: Gas,
 
+    #[serde(with = "coded_map")]
     pub(crate) hashing_cost: HashMap<SupportedHashes, ScalingCost>,
 
     pub(crate) compute_unsealed_sector_cid_base: Gas,
     pub(crate) verify_seal_base: Gas,
+    #[serde(with = "coded_map")]
     pub(crate) verify_aggregate_seal_per: HashMap<RegisteredSealProof, Gas>,
+    #[serde(with = "coded_map")]
     pub(crate) verify_aggregate_seal_steps: HashMap<RegisteredSealProof, StepCost>,
 
+    #[serde(with = "coded_map")]
     pub(crate) verify_post_lookup: HashMap<RegisteredPoStProof, ScalingCost>,
     pub(crate) verify_consensus_fault: Gas,
     pub(crate) verify_replica_update: Gas,
@@ -451,6 +470,10 @@ pub struct PriceList {
     pub(crate) event_per_entry_cost: Gas,
     pub(crate) event_entry_index_cost: Gas,
     pub(crate) event_per_byte_cost: Gas,
+    /// Gas cost per byte of validating event entry values, only charged when events are
+    /// validated (see [`NetworkConfig::validate_events`](crate::machine::NetworkConfig)).
+    #[serde(default)]
+    pub(crate) event_validation_per_byte_cost: Gas,
 
     /// Gas cost of looking up an actor in the common state tree.
     ///
@@ -476,7 +499,8 @@ pub struct PriceList {
     pub(crate) install_wasm_per_byte_cost: Gas,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct WasmGasPrices {
     /// The default gas cost for instructions.
     pub(crate) instruction_default: Gas,
@@ -894,8 +918,8 @@ impl PriceList {
     }
 
     #[inline]
-    pub fn on_actor_event(&self, evt: &ActorEvent) -> GasCharge {
-        let (mut indexed_entries, mut total_bytes) = (0, 0);
+    pub fn on_actor_event(&self, evt: &ActorEvent, validate: bool) -> GasCharge {
+        let (mut indexed_entries, mut total_bytes, mut value_bytes) = (0, 0, 0);
         for evt in evt.entries.iter() {
             indexed_entries += evt
                 .flags
@@ -903,23 +927,32 @@ impl PriceList {
                 .bits()
                 .count_ones();
             total_bytes += evt.key.len() + evt.value.bytes().len();
+            value_bytes += evt.value.bytes().len();
         }
+        let validation = if validate {
+            self.event_validation_per_byte_cost * value_bytes
+        } else {
+            Gas::zero()
+        };
 
         GasCharge::new(
             "OnActorEvent",
-            self.event_emit_base_cost + (self.event_per_entry_cost * evt.entries.len()),
+            self.event_emit_base_cost
+                + (self.event_per_entry_cost * evt.entries.len())
+                + validation,
             (self.event_entry_index_cost * indexed_entries)
                 + (self.event_per_byte_cost * total_bytes),
         )
     }
 }
 
-/// Returns gas price list by NetworkVersion for gas consumption.
-pub fn price_list_by_network_version(network_version: NetworkVersion) -> &'static PriceList {
+/// Returns gas price list by NetworkVersion for gas consumption. The price lists are shared, so
+/// this doesn't copy them.
+pub fn price_list_by_network_version(network_version: NetworkVersion) -> Arc<PriceList> {
     match network_version {
-        NetworkVersion::V18 => &HYGGE_PRICES,
+        NetworkVersion::V18 => HYGGE_PRICES.clone(),
         #[cfg(feature = "hyperspace")]
-        _ if network_version > NetworkVersion::V18 => &HYGGE_PRICES,
+        _ if network_version > NetworkVersion::V18 => HYGGE_PRICES.clone(),
         _ => panic!("network version {nv} not supported", nv = network_version),
     }
 }
@@ -1232,6 +1265,250 @@ impl Rules for WasmGasPrices {
     }
 }
 
+/// The formats price lists can be loaded from and saved to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceListFormat {
+    Toml,
+    Json,
+    Cbor,
+}
+
+impl PriceListFormat {
+    /// Determines the format from a file's extension (`.toml`, `.json`, or `.cbor`).
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(Self::Toml),
+            Some("json") => Ok(Self::Json),
+            Some("cbor") => Ok(Self::Cbor),
+            _ => Err(anyhow!(
+                "unknown price list format for {}: expected a .toml, .json, or .cbor file",
+                path.display()
+            )),
+        }
+    }
+}
+
+impl PriceList {
+    /// Loads and validates a price list from a file, determining the format from the file's
+    /// extension.
+    ///
+    /// The easiest way to get started is to save an existing price list (see [`PriceList::encode`]
+    /// and [`price_list_by_network_version`]) and edit it.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let format = PriceListFormat::from_path(path)?;
+        let data = std::fs::read(path)
+            .with_context(|| format!("failed to read price list {}", path.display()))?;
+        Self::decode(&data, format)
+            .with_context(|| format!("failed to load price list {}", path.display()))
+    }
+
+    /// Decodes and validates a price list.
+    pub fn decode(data: &[u8], format: PriceListFormat) -> anyhow::Result<Self> {
+        let price_list: PriceList = match format {
+            PriceListFormat::Toml => toml::from_slice(data)?,
+            PriceListFormat::Json => serde_json::from_slice(data)?,
+            PriceListFormat::Cbor => fvm_ipld_encoding::from_slice(data)?,
+        };
+        price_list.validate()?;
+        Ok(price_list)
+    }
+
+    /// Encodes the price list in the given format.
+    pub fn encode(&self, format: PriceListFormat) -> anyhow::Result<Vec<u8>> {
+        Ok(match format {
+            // Go through a `toml::Value` so that tables are emitted after plain values, as TOML
+            // requires.
+            PriceListFormat::Toml => toml::to_string_pretty(&toml::Value::try_from(self)?)?.into(),
+            PriceListFormat::Json => serde_json::to_vec_pretty(self)?,
+            PriceListFormat::Cbor => fvm_ipld_encoding::to_vec(self)?,
+        })
+    }
+
+    /// Checks that the price list can safely be used to price messages. That is:
+    ///
+    /// - All signature types and hash functions are priced.
+    /// - The proof types used as fallbacks for unpriced proofs are priced.
+    /// - Steps are sorted by their start.
+    /// - Wasm instruction prices are non-negative and fit in 32 bits (in milligas).
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for sig_type in HYGGE_PRICES.sig_cost.keys() {
+            if !self.sig_cost.contains_key(sig_type) {
+                bail!("sig_cost: missing signature type {:?}", sig_type);
+            }
+        }
+        for hasher in HYGGE_PRICES.hashing_cost.keys() {
+            if !self.hashing_cost.contains_key(hasher) {
+                bail!("hashing_cost: missing hash function {:?}", hasher);
+            }
+        }
+
+        let seal_fallback = RegisteredSealProof::StackedDRG32GiBV1P1;
+        if !self.verify_aggregate_seal_per.contains_key(&seal_fallback) {
+            bail!(
+                "verify_aggregate_seal_per: missing fallback proof type {:?}",
+                seal_fallback
+            );
+        }
+        if !self
+            .verify_aggregate_seal_steps
+            .contains_key(&seal_fallback)
+        {
+            bail!(
+                "verify_aggregate_seal_steps: missing fallback proof type {:?}",
+                seal_fallback
+            );
+        }
+        let post_fallback = RegisteredPoStProof::StackedDRGWindow512MiBV1;
+        if !self.verify_post_lookup.contains_key(&post_fallback) {
+            bail!(
+                "verify_post_lookup: missing fallback proof type {:?}",
+                post_fallback
+            );
+        }
+
+        for (proof, steps) in &self.verify_aggregate_seal_steps {
+            if steps.0.windows(2).any(|w| w[0].start >= w[1].start) {
+                bail!(
+                    "verify_aggregate_seal_steps: steps for {:?} aren't sorted by start",
+                    proof
+                );
+            }
+        }
+
+        // Destructure so we don't forget to update this when adding new fields.
+        let WasmGasPrices {
+            instruction_default,
+            math_default,
+            jump_unconditional,
+            jump_conditional,
+            jump_indirect,
+            call,
+            memory_fill_base_cost,
+            memory_fill_per_byte_cost,
+            memory_access_cost,
+            memory_copy_per_byte_cost,
+        } = &self.wasm_rules;
+        for (name, price) in [
+            ("instruction_default", instruction_default),
+            ("math_default", math_default),
+            ("jump_unconditional", jump_unconditional),
+            ("jump_conditional", jump_conditional),
+            ("jump_indirect", jump_indirect),
+            ("call", call),
+            ("memory_fill_base_cost", memory_fill_base_cost),
+            ("memory_fill_per_byte_cost", memory_fill_per_byte_cost),
+            ("memory_access_cost", memory_access_cost),
+            ("memory_copy_per_byte_cost", memory_copy_per_byte_cost),
+        ] {
+            if u32::try_from(price.as_milligas()).is_err() {
+                bail!(
+                    "wasm_rules.{}: {} milligas is out of range",
+                    name,
+                    price.as_milligas()
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Types used to key price tables, identified by a numeric code when serialized.
+trait Coded: Sized + Eq + std::hash::Hash {
+    fn code(&self) -> i64;
+    fn from_code(code: i64) -> Option<Self>;
+}
+
+impl Coded for SignatureType {
+    fn code(&self) -> i64 {
+        *self as i64
+    }
+
+    fn from_code(code: i64) -> Option<Self> {
+        SignatureType::from_i64(code)
+    }
+}
+
+impl Coded for SupportedHashes {
+    fn code(&self) -> i64 {
+        u64::from(*self) as i64
+    }
+
+    fn from_code(code: i64) -> Option<Self> {
+        SupportedHashes::try_from(u64::try_from(code).ok()?).ok()
+    }
+}
+
+impl Coded for RegisteredSealProof {
+    fn code(&self) -> i64 {
+        i64::from(*self)
+    }
+
+    fn from_code(code: i64) -> Option<Self> {
+        match RegisteredSealProof::from(code) {
+            RegisteredSealProof::Invalid(_) => None,
+            proof => Some(proof),
+        }
+    }
+}
+
+impl Coded for RegisteredPoStProof {
+    fn code(&self) -> i64 {
+        i64::from(*self)
+    }
+
+    fn from_code(code: i64) -> Option<Self> {
+        match RegisteredPoStProof::from(code) {
+            RegisteredPoStProof::Invalid(_) => None,
+            proof => Some(proof),
+        }
+    }
+}
+
+/// (De)serializes maps keyed by [`Coded`] types as maps keyed by their (decimal) codes. The codes
+/// are formatted as strings because TOML and JSON only support string keys.
+mod coded_map {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Coded;
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Coded,
+        V: Serialize,
+        S: Serializer,
+    {
+        // Sort the entries so the output is deterministic.
+        let sorted: BTreeMap<i64, &V> = map.iter().map(|(k, v)| (k.code(), v)).collect();
+        serializer.collect_map(sorted.into_iter().map(|(k, v)| (k.to_string(), v)))
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Coded,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let raw = BTreeMap::<String, V>::deserialize(deserializer)?;
+        let mut map = HashMap::with_capacity(raw.len());
+        for (k, v) in raw {
+            let code: i64 = k
+                .parse()
+                .map_err(|_| D::Error::custom(format!("invalid key {:?}: expected a code", k)))?;
+            let key = K::from_code(code)
+                .ok_or_else(|| D::Error::custom(format!("unknown code {}", code)))?;
+            if map.insert(key, v).is_some() {
+                return Err(D::Error::custom(format!("duplicate code {}", code)));
+            }
+        }
+        Ok(map)
+    }
+}
+
 #[test]
 fn test_read_write() {
     // The math for these operations is complicated, so we explicitly test to make sure we're
@@ -1242,3 +1519,89 @@ fn test_read_write() {
     );
     assert_eq!(HYGGE_PRICES.on_block_create(10).total(), Gas::new(100));
 }
+
+#[test]
+fn test_actor_event_validation() {
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::event::Entry;
+
+    let evt = ActorEvent::from(vec![Entry {
+        flags: Flags::empty(),
+        key: "foo".into(),
+        value: RawBytes::new(vec![0x00; 10]),
+    }]);
+    let unvalidated = HYGGE_PRICES.on_actor_event(&evt, false);
+    let validated = HYGGE_PRICES.on_actor_event(&evt, true);
+    assert_eq!(
+        validated.compute_gas - unvalidated.compute_gas,
+        Gas::new(10)
+    );
+    assert_eq!(validated.other_gas, unvalidated.other_gas);
+}
+
+#[test]
+fn test_price_list_roundtrip() {
+    for format in [
+        PriceListFormat::Toml,
+        PriceListFormat::Json,
+        PriceListFormat::Cbor,
+    ] {
+        let encoded = HYGGE_PRICES.encode(format).unwrap();
+        let decoded = PriceList::decode(&encoded, format).unwrap();
+        assert_eq!(decoded, **HYGGE_PRICES, "{:?}", format);
+    }
+}
+
+#[test]
+fn test_price_list_validate() {
+    HYGGE_PRICES.validate().unwrap();
+
+    let mut prices = PriceList::clone(&HYGGE_PRICES);
+    prices.wasm_rules.call = Gas::from_milligas(-1);
+    assert!(prices.validate().is_err());
+
+    let mut prices = PriceList::clone(&HYGGE_PRICES);
+    prices.wasm_rules.call = Gas::from_milligas(1 << 32);
+    assert!(prices.validate().is_err());
+
+    let mut prices = PriceList::clone(&HYGGE_PRICES);
+    prices.hashing_cost.remove(&SupportedHashes::Keccak256);
+    assert!(prices.validate().is_err());
+
+    let mut prices = PriceList::clone(&HYGGE_PRICES);
+    prices
+        .verify_post_lookup
+        .remove(&RegisteredPoStProof::StackedDRGWindow512MiBV1);
+    assert!(prices.validate().is_err());
+
+    let mut prices = PriceList::clone(&HYGGE_PRICES);
+    for steps in prices.verify_aggregate_seal_steps.values_mut() {
+        steps.0.reverse();
+    }
+    assert!(prices.validate().is_err());
+}
+
+#[test]
+fn test_price_list_decode_invalid() {
+    let json = String::from_utf8(HYGGE_PRICES.encode(PriceListFormat::Json).unwrap()).unwrap();
+
+    // Fields added since the first price lists may be omitted.
+    let mut missing_field: serde_json::Value = serde_json::from_str(&json).unwrap();
+    missing_field
+        .as_object_mut()
+        .unwrap()
+        .remove("event_validation_per_byte_cost")
+        .unwrap();
+    let missing_field = serde_json::to_vec(&missing_field).unwrap();
+    let decoded = PriceList::decode(&missing_field, PriceListFormat::Json).unwrap();
+    assert_eq!(decoded.event_validation_per_byte_cost, Gas::zero());
+
+    // Unknown fields are rejected.
+    let unknown_field = json.replacen('{', r#"{"unknown": 1,"#, 1);
+    assert!(PriceList::decode(unknown_field.as_bytes(), PriceListFormat::Json).is_err());
+
+    // As are unknown codes (Secp256k1 is 1).
+    let unknown_code = json.replacen(r#""1": {"#, r#""42": {"#, 1);
+    assert_ne!(unknown_code, json);
+    assert!(PriceList::decode(unknown_code.as_bytes(), PriceListFormat::Json).is_err());
+}
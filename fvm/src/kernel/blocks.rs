@@ -1,18 +1,25 @@
 // Copyright 2021-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::collections::HashSet;
 use std::convert::TryInto;
+use std::io::Cursor;
 use std::rc::Rc;
 
+use cid::Cid;
 use fvm_ipld_encoding::{CBOR, DAG_CBOR};
 use fvm_shared::IPLD_RAW;
 use thiserror::Error;
 
 use super::{ExecutionError, SyscallError};
+use crate::blockstore::scan_for_links;
 use crate::syscall_error;
 
 #[derive(Default)]
 pub struct BlockRegistry {
     blocks: Vec<Block>,
+    /// The CIDs the actor may open or set as its state root. This only grows over the course of
+    /// an invocation.
+    reachable: HashSet<Cid>,
 }
 
 /// Blocks in the block registry are addressed by an ordinal, starting from 1 (`FIRST_ID`).
@@ -24,7 +31,7 @@ const FIRST_ID: BlockId = 1;
 const MAX_BLOCKS: u32 = i32::MAX as u32; // TODO(M2): Limit
 
 /// Codecs allowed by the IPLD subsytem.
-const ALLOWED_CODECS: &[u64; 3] = &[CBOR, DAG_CBOR, IPLD_RAW];
+pub(crate) const ALLOWED_CODECS: &[u64; 3] = &[CBOR, DAG_CBOR, IPLD_RAW];
 
 #[derive(Debug, Copy, Clone)]
 pub struct BlockStat {
@@ -116,7 +123,7 @@ impl From<InvalidHandleError> for ExecutionError {
 
 impl BlockRegistry {
     pub(crate) fn new() -> Self {
-        Self { blocks: Vec::new() }
+        Self::default()
     }
 }
 
@@ -136,6 +143,37 @@ impl BlockRegistry {
         Ok(id)
     }
 
+    /// Adds a block the actor is allowed to traverse (e.g., a block read from the state, or the
+    /// parameters), marking all of its links reachable. Returns a handle to refer to the block.
+    ///
+    /// Only links in DAG_CBOR blocks are followed, and a block that can't be parsed doesn't make
+    /// anything reachable.
+    pub fn put_reachable(&mut self, block: Block) -> Result<BlockId, BlockPutError> {
+        let mut links = Vec::new();
+        if block.codec() == DAG_CBOR
+            && scan_for_links(&mut Cursor::new(block.data()), |link| {
+                links.push(link);
+                Ok(())
+            })
+            .is_err()
+        {
+            links.clear();
+        }
+        let id = self.put(block)?;
+        self.reachable.extend(links);
+        Ok(id)
+    }
+
+    /// Marks a CID as reachable.
+    pub fn mark_reachable(&mut self, cid: Cid) {
+        self.reachable.insert(cid);
+    }
+
+    /// Returns true if the CID has been marked reachable.
+    pub fn is_reachable(&self, cid: &Cid) -> bool {
+        self.reachable.contains(cid)
+    }
+
     /// Gets the block associated with a block handle.
     pub fn get(&self, id: BlockId) -> Result<&Block, InvalidHandleError> {
         if id < FIRST_ID {
@@ -166,3 +204,44 @@ impl BlockRegistry {
         self.blocks.len() as u32 == MAX_BLOCKS
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+
+    use super::*;
+
+    #[test]
+    fn reachability() {
+        let cid = |data: &[u8]| Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(data));
+        let (a, b, c) = (cid(b"a"), cid(b"b"), cid(b"c"));
+        let links = fvm_ipld_encoding::to_vec(&(a, b)).unwrap();
+
+        let mut registry = BlockRegistry::new();
+        registry.put(Block::new(DAG_CBOR, links.clone())).unwrap();
+        assert!(!registry.is_reachable(&a));
+
+        // Only links in DAG_CBOR blocks count.
+        registry
+            .put_reachable(Block::new(CBOR, links.clone()))
+            .unwrap();
+        assert!(!registry.is_reachable(&a));
+
+        registry.put_reachable(Block::new(DAG_CBOR, links)).unwrap();
+        assert!(registry.is_reachable(&a));
+        assert!(registry.is_reachable(&b));
+        assert!(!registry.is_reachable(&c));
+
+        registry.mark_reachable(c);
+        assert!(registry.is_reachable(&c));
+
+        // Nothing is reachable from a block that can't be parsed, even if it starts with a link.
+        let mut invalid = fvm_ipld_encoding::to_vec(&(c, a)).unwrap();
+        invalid[0] = 0x83; // A 3-element array.
+        let mut registry = BlockRegistry::new();
+        registry
+            .put_reachable(Block::new(DAG_CBOR, invalid))
+            .unwrap();
+        assert!(!registry.is_reachable(&c));
+    }
+}
@@ -2,14 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
+use std::io::Cursor;
 use std::panic::{self, UnwindSafe};
-use std::path::PathBuf;
 
 use anyhow::{anyhow, Context as _};
 use cid::Cid;
 use filecoin_proofs_api::{self as proofs, ProverId, PublicReplicaInfo, SectorId};
 use fvm_ipld_blockstore::Blockstore;
-use fvm_ipld_encoding::bytes_32;
+use fvm_ipld_encoding::{bytes_32, DAG_CBOR};
 use fvm_shared::address::Payload;
 use fvm_shared::bigint::Zero;
 use fvm_shared::consensus::ConsensusFault;
@@ -25,10 +25,12 @@ use multihash::MultihashDigest;
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use rayon::prelude::ParallelDrainRange;
 
-use super::blocks::{Block, BlockRegistry};
+use super::blocks::{Block, BlockRegistry, ALLOWED_CODECS};
 use super::error::Result;
 use super::hash::SupportedHashes;
 use super::*;
+use crate::artifact::{ArtifactSink, ArtifactSource, FilesystemArtifactSink, ENV_ARTIFACT_DIR};
+use crate::blockstore::scan_for_links;
 use crate::call_manager::{CallManager, InvocationResult, NO_DATA_BLOCK_ID};
 use crate::externs::{Chain, Consensus, Rand};
 use crate::gas::GasTimer;
@@ -36,6 +38,7 @@ use crate::init_actor::INIT_ACTOR_ID;
 use crate::machine::{MachineContext, NetworkConfig};
 use crate::state_tree::ActorState;
 use crate::syscall_error;
+use crate::trace::{ActorLog, ExecutionEvent};
 
 lazy_static! {
     static ref NUM_CPUS: usize = num_cpus::get();
@@ -43,7 +46,6 @@ lazy_static! {
 }
 
 const BLAKE2B_256: u64 = 0xb220;
-const ENV_ARTIFACT_DIR: &str = "FVM_STORE_ARTIFACT_DIR";
 const MAX_ARTIFACT_NAME_LEN: usize = 256;
 const FINALITY: i64 = 900;
 
@@ -58,14 +60,14 @@ pub struct DefaultKernel<C> {
     actor_id: ActorID,
     method: MethodNum,
     value_received: TokenAmount,
+    /// The call manager's invocation count when this kernel was created.
+    invocation: u64,
 
     /// The call manager for this call stack. If this kernel calls another actor, it will
     /// temporarily "give" the call manager to the other kernel before re-attaching it.
     call_manager: C,
     /// Tracks block data and organizes it through index handles so it can be
-    /// referred to.
-    ///
-    /// This does not yet reason about reachability.
+    /// referred to, along with the set of CIDs reachable by this invocation.
     blocks: BlockRegistry,
 }
 
@@ -93,6 +95,7 @@ where
         value_received: TokenAmount,
     ) -> Self {
         DefaultKernel {
+            invocation: mgr.invocation_count(),
             call_manager: mgr,
             blocks,
             caller,
@@ -137,6 +140,44 @@ where
                 }
             })
     }
+
+    /// Returns true if this actor may open `cid` or set it as its state root: either it was
+    /// marked reachable in this invocation, or it's the actor's current state root.
+    ///
+    /// We check the state root on demand (instead of seeding the reachable set with it) because
+    /// it may change during the invocation if the actor is re-entered.
+    fn is_reachable(&self, cid: &Cid) -> Result<bool> {
+        Ok(self.blocks.is_reachable(cid) || self.get_self()?.map(|a| a.state) == Some(*cid))
+    }
+
+    /// Fails with `NotFound` if a DAG_CBOR block this actor is creating links to a block it can't
+    /// reach. Otherwise, the actor could reach any block by linking to it from a new block, then
+    /// opening that block.
+    ///
+    /// Links to blocks actors can't open anyway (e.g., commitments) and links to builtin actor code
+    /// are always allowed.
+    fn check_links_reachable(&self, data: &[u8]) -> Result<()> {
+        let mut links = Vec::new();
+        if scan_for_links(&mut Cursor::new(data), |link| {
+            links.push(link);
+            Ok(())
+        })
+        .is_err()
+        {
+            // Nothing is reachable from a block that can't be parsed.
+            return Ok(());
+        }
+        let builtin_actors = self.call_manager.machine().builtin_actors();
+        for link in links {
+            if !ALLOWED_CODECS.contains(&link.codec()) || builtin_actors.id_by_code(&link) != 0 {
+                continue;
+            }
+            if !self.is_reachable(&link)? {
+                return Err(syscall_error!(NotFound; "unreachable link: {}", link).into());
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<C> SelfOps for DefaultKernel<C>
@@ -163,10 +204,22 @@ where
             .call_manager
             .charge_gas(self.call_manager.price_list().on_set_root())?;
 
+        if self.call_manager.context().enforce_reachability && !self.is_reachable(&new)? {
+            return Err(syscall_error!(NotFound; "new root cid not reachable: {}", new).into());
+        }
+
         t.record(self.mutate_self(|actor_state| {
             actor_state.state = new;
             Ok(())
-        }))
+        }))?;
+
+        if self.call_manager.context().tracing {
+            self.call_manager.trace(ExecutionEvent::SetRoot {
+                actor: self.actor_id,
+                cid: new,
+            });
+        }
+        Ok(())
     }
 
     fn current_balance(&self) -> Result<TokenAmount> {
@@ -217,26 +270,35 @@ where
     C: CallManager,
 {
     fn block_open(&mut self, cid: &Cid) -> Result<(BlockId, BlockStat)> {
-        // TODO(M2): Check for reachability here.
-
         let _ = self
             .call_manager
             .charge_gas(self.call_manager.price_list().on_block_open_base())?;
 
         let start = GasTimer::start();
 
-        let data = self
+        let enforce_reachability = self.call_manager.context().enforce_reachability;
+        if enforce_reachability && !self.is_reachable(cid)? {
+            return Err(syscall_error!(NotFound; "block not reachable: {}", cid).into());
+        }
+
+        let data = match self
             .call_manager
             .blockstore()
             .get(cid)
             // TODO: This is really "super fatal". It means we failed to store state, and should
             // probably abort the entire block.
             .or_fatal()?
-            .ok_or_else(|| anyhow!("missing state: {}", cid))
-            // Missing state is a fatal error because it means we have a bug. Once we do
-            // reachability checking (for user actors) we won't get here unless the block is known
-            // to be in the state-tree.
-            .or_fatal()?;
+        {
+            Some(data) => data,
+            // Reachable blocks may still be missing: the links in the message parameters needn't
+            // point to anything. So this is on the caller, not a bug in the state.
+            None if enforce_reachability => {
+                return Err(syscall_error!(NotFound; "missing state: {}", cid).into())
+            }
+            // Without reachability checks, missing state is a fatal error because it means we
+            // have a bug.
+            None => return Err(anyhow!("missing state: {}", cid)).or_fatal(),
+        };
 
         let block = Block::new(cid.codec(), data);
 
@@ -247,8 +309,17 @@ where
         )?;
 
         let stat = block.stat();
-        let id = self.blocks.put(block)?;
+        let id = self.blocks.put_reachable(block)?;
         t.stop_with(start);
+
+        if self.call_manager.context().tracing {
+            self.call_manager.trace(ExecutionEvent::BlockOpen {
+                actor: self.actor_id,
+                cid: *cid,
+                codec: stat.codec,
+                size: stat.size,
+            });
+        }
         Ok((id, stat))
     }
 
@@ -261,6 +332,10 @@ where
             .call_manager
             .charge_gas(self.call_manager.price_list().on_block_create(data.len()))?;
 
+        if codec == DAG_CBOR && self.call_manager.context().enforce_reachability {
+            self.check_links_reachable(data)?;
+        }
+
         t.record(Ok(self.blocks.put(Block::new(codec, data))?))
     }
 
@@ -284,7 +359,6 @@ where
             return Err(syscall_error!(IllegalCid; "invalid hash length: {}", hash_len).into());
         }
         let k = Cid::new_v1(block.codec(), hash.truncate(hash_len as u8));
-        // TODO(M2): Add the block to the reachable set.
         self.call_manager
             .blockstore()
             .put_keyed(&k, block.data())
@@ -292,6 +366,16 @@ where
             // probably abort the entire block.
             .or_fatal()?;
         t.stop_with(start);
+
+        if self.call_manager.context().tracing {
+            self.call_manager.trace(ExecutionEvent::BlockLink {
+                actor: self.actor_id,
+                cid: k,
+                codec: block.codec(),
+                size: block.size(),
+            });
+        }
+        self.blocks.mark_reachable(k);
         Ok(k)
     }
 
@@ -415,10 +499,11 @@ where
                 exit_code,
                 value: Some(blk),
             } => {
+                // The caller may traverse anything the return value links to.
                 let block_stat = blk.stat();
                 let block_id = self
                     .blocks
-                    .put(blk)
+                    .put_reachable(blk)
                     .or_fatal()
                     .context("failed to store a valid return value")?;
                 SendResult {
@@ -923,8 +1008,18 @@ impl<C> DebugOps for DefaultKernel<C>
 where
     C: CallManager,
 {
-    fn log(&self, msg: String) {
-        println!("{}", msg)
+    fn log(&mut self, msg: String) {
+        let log = ActorLog {
+            actor: self.actor_id,
+            method: self.method,
+            invocation: self.invocation,
+            nonce: self.call_manager.nonce(),
+            msg,
+        };
+        log::info!(target: "fvm::actor", "{}", log);
+        if self.call_manager.context().collect_actor_logs {
+            self.call_manager.append_log(log);
+        }
     }
 
     fn debug_enabled(&self) -> bool {
@@ -952,31 +1047,28 @@ where
         }
         .or_error(fvm_shared::error::ErrorNumber::IllegalArgument)?;
 
-        // Write to disk
-        if let Ok(dir) = std::env::var(ENV_ARTIFACT_DIR).as_deref() {
-            let dir: PathBuf = [
-                dir,
-                self.call_manager.machine().machine_id(),
-                &self.call_manager.origin().to_string(),
-                &self.call_manager.nonce().to_string(),
-                &self.actor_id.to_string(),
-                &self.call_manager.invocation_count().to_string(),
-            ]
-            .iter()
-            .collect();
-
-            if let Err(e) = std::fs::create_dir_all(dir.clone()) {
-                log::error!("failed to make directory to store debug artifacts {}", e);
-            } else if let Err(e) = std::fs::write(dir.join(name), data) {
-                log::error!("failed to store debug artifact {}", e)
-            } else {
-                log::info!("wrote artifact: {} to {:?}", name, dir);
-            }
-        } else {
-            log::error!(
-                "store_artifact was ignored, env var {} was not set",
-                ENV_ARTIFACT_DIR
-            )
+        let source = ArtifactSource {
+            machine_id: self.call_manager.machine().machine_id().to_owned(),
+            origin: self.call_manager.origin(),
+            nonce: self.call_manager.nonce(),
+            actor: self.actor_id,
+            invocation: self.invocation,
+        };
+        let res = match &self.call_manager.context().artifact_sink {
+            Some(sink) => sink.store(&source, name, data),
+            None => match FilesystemArtifactSink::from_env() {
+                Some(sink) => sink.store(&source, name, data),
+                None => {
+                    log::error!(
+                        "store_artifact was ignored, no sink installed and env var {} not set",
+                        ENV_ARTIFACT_DIR
+                    );
+                    Ok(())
+                }
+            },
+        };
+        if let Err(e) = res {
+            log::error!("failed to store debug artifact {}: {:#}", name, e);
         }
         Ok(())
     }
@@ -998,12 +1090,17 @@ where
     C: CallManager,
 {
     fn emit_event(&mut self, evt: ActorEvent) -> Result<()> {
-        let t = self
-            .call_manager
-            .charge_gas(self.call_manager.price_list().on_actor_event(&evt))?;
+        let validate = self.call_manager.context().validate_events;
+        let t = self.call_manager.charge_gas(
+            self.call_manager
+                .price_list()
+                .on_actor_event(&evt, validate),
+        )?;
 
-        // TODO eventually validate entries
-        //  https://github.com/filecoin-project/ref-fvm/issues/1082
+        let config = self.call_manager.context();
+        if validate {
+            validate_event(&evt, config)?;
+        }
 
         let evt = StampedEvent::new(self.actor_id, evt);
         self.call_manager.append_event(evt);
@@ -1012,6 +1109,41 @@ where
     }
 }
 
+/// Checks an actor event against the network's event limits.
+fn validate_event(evt: &ActorEvent, config: &NetworkConfig) -> Result<()> {
+    if evt.entries.len() > config.max_event_entries {
+        return Err(syscall_error!(IllegalArgument;
+            "event has too many entries: {}", evt.entries.len())
+        .into());
+    }
+    for entry in &evt.entries {
+        if entry.key.len() > config.max_event_key_len {
+            return Err(syscall_error!(IllegalArgument;
+                "event key is too long: {} bytes", entry.key.len())
+            .into());
+        }
+        if !config.allowed_event_flags.contains(entry.flags) {
+            return Err(syscall_error!(IllegalArgument;
+                "event entry {} has invalid flags: {:#010b}", entry.key, entry.flags.bits())
+            .into());
+        }
+        let value = entry.value.bytes();
+        if value.len() > config.max_event_value_size {
+            return Err(syscall_error!(IllegalArgument;
+                "event value for {} is too large: {} bytes", entry.key, value.len())
+            .into());
+        }
+        if config.require_dag_cbor_event_values
+            && fvm_ipld_encoding::validate_dag_cbor(value).is_err()
+        {
+            return Err(syscall_error!(IllegalArgument;
+                "event value for {} is not valid DAG-CBOR", entry.key)
+            .into());
+        }
+    }
+    Ok(())
+}
+
 fn catch_and_log_panic<F: FnOnce() -> Result<R> + UnwindSafe, R>(context: &str, f: F) -> Result<R> {
     match panic::catch_unwind(f) {
         Ok(v) => v,
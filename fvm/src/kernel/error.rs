@@ -4,6 +4,7 @@ use std::fmt::Display;
 
 use derive_more::Display;
 use fvm_shared::error::ErrorNumber;
+use serde::{Deserialize, Serialize};
 
 /// Execution result.
 pub type Result<T> = std::result::Result<T, ExecutionError>;
@@ -177,9 +178,12 @@ impl From<ExecutionError> for anyhow::Error {
 /// We may want to add an optional source error here.
 ///
 /// Automatic conversions from String are provided, with no advised exit code.
-#[derive(thiserror::Error, Debug, Clone)]
+#[derive(thiserror::Error, Debug, Clone, Serialize, Deserialize)]
 #[error("syscall error: {0} (exit_code={1:?})")]
-pub struct SyscallError(pub String, pub ErrorNumber);
+pub struct SyscallError(
+    pub String,
+    #[serde(with = "crate::export::human::error_number")] pub ErrorNumber,
+);
 
 impl SyscallError {
     pub fn new<D: Display>(c: ErrorNumber, d: D) -> Self {
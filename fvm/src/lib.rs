@@ -11,6 +11,8 @@
 pub use kernel::default::DefaultKernel;
 pub use kernel::Kernel;
 
+pub mod artifact;
+pub mod blockstore;
 pub mod call_manager;
 pub mod engine;
 pub mod executor;
@@ -22,8 +24,6 @@ pub mod syscalls;
 pub mod gas;
 pub mod state_tree;
 
-mod blockstore;
-
 #[cfg(not(feature = "testing"))]
 mod account_actor;
 #[cfg(not(feature = "testing"))]
@@ -39,6 +39,7 @@ pub mod init_actor;
 pub mod system_actor;
 
 mod eam_actor;
+pub mod export;
 pub mod trace;
 
 use cid::multihash::{Code, MultihashDigest};
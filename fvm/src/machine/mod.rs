@@ -1,5 +1,7 @@
 // Copyright 2021-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::sync::Arc;
+
 use cid::Cid;
 use derive_more::{Deref, DerefMut};
 use fvm_ipld_blockstore::Blockstore;
@@ -10,6 +12,8 @@ use fvm_shared::version::NetworkVersion;
 use fvm_shared::ActorID;
 use num_traits::Zero;
 
+use crate::artifact::ArtifactSink;
+use crate::call_manager::ExecutionObserver;
 use crate::externs::Externs;
 use crate::gas::{price_list_by_network_version, PriceList};
 use crate::kernel::Result;
@@ -23,7 +27,7 @@ use fvm_shared::chainid::ChainID;
 pub mod limiter;
 mod manifest;
 
-use fvm_shared::event::StampedEvent;
+use fvm_shared::event::{Flags, StampedEvent};
 pub use manifest::Manifest;
 
 use self::limiter::MemoryLimiter;
@@ -32,6 +36,9 @@ mod boxed;
 
 pub const REWARD_ACTOR_ID: ActorID = 2;
 
+/// Distinguished cron actor, invoked at the end of every tipset.
+pub const CRON_ACTOR_ID: ActorID = 3;
+
 /// Distinguished Account actor that is the destination of all burnt funds.
 pub const BURNT_FUNDS_ACTOR_ID: ActorID = 99;
 
@@ -150,10 +157,46 @@ pub struct NetworkConfig {
     /// The price list.
     ///
     /// DEFAULT: The price-list for the current network version.
-    pub price_list: &'static PriceList,
+    pub price_list: Arc<PriceList>,
 
     /// Actor redirects for debug execution
     pub actor_redirect: Vec<(Cid, Cid)>,
+
+    /// Whether actors may only open, link to, and set as their state root the blocks reachable
+    /// from their state, parameters, and return values, and the blocks they create.
+    ///
+    /// DEFAULT: `true` after nv18, `false` otherwise.
+    pub enforce_reachability: bool,
+
+    /// Whether to check actor events against the limits below, rejecting events that exceed them.
+    ///
+    /// DEFAULT: `true` after nv18, `false` otherwise.
+    pub validate_events: bool,
+
+    /// The maximum number of entries in an actor event.
+    ///
+    /// DEFAULT: 255
+    pub max_event_entries: usize,
+
+    /// The maximum length of an actor event entry's key, in bytes.
+    ///
+    /// DEFAULT: 31
+    pub max_event_key_len: usize,
+
+    /// The maximum size of an actor event entry's value, in bytes.
+    ///
+    /// DEFAULT: 8KiB
+    pub max_event_value_size: usize,
+
+    /// The flags actor event entries may set.
+    ///
+    /// DEFAULT: `Flags::all()`
+    pub allowed_event_flags: Flags,
+
+    /// Whether actor event entry values must be well-formed DAG-CBOR.
+    ///
+    /// DEFAULT: `true`
+    pub require_dag_cbor_event_values: bool,
 }
 
 impl NetworkConfig {
@@ -170,7 +213,14 @@ impl NetworkConfig {
             builtin_actors_override: None,
             price_list: price_list_by_network_version(network_version),
             actor_redirect: vec![],
+            enforce_reachability: network_version > NetworkVersion::V18,
             max_block_size: 1 << 20,
+            validate_events: network_version > NetworkVersion::V18,
+            max_event_entries: 255,
+            max_event_key_len: 31,
+            max_event_value_size: 8 << 10,
+            allowed_event_flags: Flags::all(),
+            require_dag_cbor_event_values: true,
         }
     }
 
@@ -181,6 +231,20 @@ impl NetworkConfig {
         self
     }
 
+    /// Enforce IPLD reachability checks. This is a consensus-critical option so it should only be
+    /// enabled for local testing or as a network-wide parameter.
+    pub fn enforce_reachability(&mut self) -> &mut Self {
+        self.enforce_reachability = true;
+        self
+    }
+
+    /// Validate actor events against the configured limits. This is a consensus-critical option so
+    /// it should only be enabled for local testing or as a network-wide parameter.
+    pub fn validate_events(&mut self) -> &mut Self {
+        self.validate_events = true;
+        self
+    }
+
     /// Override actors with the specific manifest. This is primarily useful for testing, or
     /// networks prior to NV16 (where the actor's "manifest" isn't specified on-chain).
     pub fn override_actors(&mut self, manifest: Cid) -> &mut Self {
@@ -188,6 +252,13 @@ impl NetworkConfig {
         self
     }
 
+    /// Override the price list, e.g., with one loaded via [`PriceList::load`]. This is a
+    /// consensus-critical option so it should only be used for local testing or experiments.
+    pub fn override_price_list(&mut self, price_list: PriceList) -> &mut Self {
+        self.price_list = Arc::new(price_list);
+        self
+    }
+
     /// Set actor redirects for debug execution
     pub fn redirect_actors(&mut self, actor_redirect: Vec<(Cid, Cid)>) -> &mut Self {
         self.actor_redirect = actor_redirect;
@@ -209,6 +280,9 @@ impl NetworkConfig {
             initial_state_root: initial_state,
             circ_supply: fvm_shared::TOTAL_FILECOIN.clone(),
             tracing: false,
+            collect_actor_logs: false,
+            observer: None,
+            artifact_sink: None,
         }
     }
 
@@ -255,6 +329,24 @@ pub struct MachineContext {
     /// Whether or not to produce execution traces in the returned result.
     /// Not consensus-critical, but has a performance impact.
     pub tracing: bool,
+
+    /// Whether or not to collect actor debug logs in the returned result. Actors only log when
+    /// [`NetworkConfig::actor_debugging`] is enabled. Not consensus-critical.
+    ///
+    /// DEFAULT: `false`
+    pub collect_actor_logs: bool,
+
+    /// An observer to notify as messages are executed. Not consensus-critical.
+    ///
+    /// DEFAULT: `None`
+    pub observer: Option<Arc<dyn ExecutionObserver>>,
+
+    /// Where to store the debug artifacts actors store. Actors only store artifacts when
+    /// [`NetworkConfig::actor_debugging`] is enabled. Not consensus-critical.
+    ///
+    /// DEFAULT: `None`, writing artifacts to the directory named by the `FVM_STORE_ARTIFACT_DIR`
+    /// environment variable, if it's set.
+    pub artifact_sink: Option<Arc<dyn ArtifactSink>>,
 }
 
 impl MachineContext {
@@ -275,4 +367,22 @@ impl MachineContext {
         self.tracing = true;
         self
     }
+
+    /// Collect actor debug logs. [`MachineContext::collect_actor_logs`].
+    pub fn enable_actor_log_collection(&mut self) -> &mut Self {
+        self.collect_actor_logs = true;
+        self
+    }
+
+    /// Install an execution observer. [`MachineContext::observer`].
+    pub fn set_observer(&mut self, observer: Arc<dyn ExecutionObserver>) -> &mut Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Install an artifact sink. [`MachineContext::artifact_sink`].
+    pub fn set_artifact_sink(&mut self, sink: Arc<dyn ArtifactSink>) -> &mut Self {
+        self.artifact_sink = Some(sink);
+        self
+    }
 }
@@ -10,10 +10,10 @@ use std::hash::Hash;
 
 use anyhow::{anyhow, Context as _};
 use cid::{multihash, Cid};
-use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore, Proof, ProofRecorder};
 use fvm_ipld_encoding::tuple::*;
 use fvm_ipld_encoding::CborStore;
-use fvm_ipld_hamt::Hamt;
+use fvm_ipld_hamt::{BytesKey, Change, Hamt};
 use fvm_shared::address::{Address, Payload};
 use fvm_shared::econ::TokenAmount;
 use fvm_shared::state::{StateInfo0, StateRoot, StateTreeVersion};
@@ -463,6 +463,64 @@ where
         }
     }
 
+    /// Computes the changes to actors from this state tree to the state tree at `other_root` (e.g.,
+    /// a root returned by [`Executor::flush`](crate::executor::Executor::flush)).
+    ///
+    /// Both actor HAMTs are walked in parallel, skipping any sub-trees they share, so this is cheap
+    /// when few actors changed. This state tree must not have any unflushed changes.
+    pub fn diff(&self, other_root: &Cid) -> Result<Vec<ActorChange>> {
+        if self.actor_cache.borrow().map.values().any(|e| e.dirty) {
+            return Err(ExecutionError::Fatal(anyhow!(
+                "cannot diff a state tree with unflushed changes"
+            )));
+        }
+        let other = StateTree::new_from_root(self.store(), other_root)?;
+        let changes = self
+            .hamt
+            .diff(&other.hamt)
+            .context("failed to diff state trees")
+            .or_fatal()?;
+
+        let actor_id = |key: &BytesKey| -> Result<ActorID> {
+            Address::from_bytes(&key.0)
+                .ok()
+                .and_then(|addr| addr.id().ok())
+                .ok_or_else(|| {
+                    ExecutionError::Fatal(anyhow!("invalid actor key in state tree: {:?}", key))
+                })
+        };
+        changes
+            .into_iter()
+            .map(|change| {
+                Ok(match change {
+                    Change::Added { key, value } => ActorChange::Added {
+                        key: actor_id(&key)?,
+                        value,
+                    },
+                    Change::Removed { key, value } => ActorChange::Removed {
+                        key: actor_id(&key)?,
+                        value,
+                    },
+                    Change::Modified { key, before, after } => ActorChange::Modified {
+                        key: actor_id(&key)?,
+                        before,
+                        after,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Generates a proof of the state of actor `id` (or of its absence) under the state root
+    /// `root`, loaded from this state tree's store. The proof is made up of the state root and the
+    /// blocks on the path to the actor in the actors HAMT, and can be checked with
+    /// [`StateTree::verify_actor_proof`].
+    pub fn prove_actor(&self, root: &Cid, id: ActorID) -> Result<Proof> {
+        let recorder = ProofRecorder::new(self.store());
+        StateTree::new_from_root(&recorder, root)?.get_actor(id)?;
+        Ok(recorder.into_proof(*root))
+    }
+
     /// Consumes this StateTree and returns the Blockstore it owns via the HAMT.
     pub fn into_store(self) -> S {
         self.hamt.into_store()
@@ -492,6 +550,49 @@ where
     }
 }
 
+impl StateTree<MemoryBlockstore> {
+    /// Verifies a proof generated by [`StateTree::prove_actor`] against the trusted state root
+    /// `root`, and returns the state of actor `id` (or `None` if it doesn't exist). Fails if the
+    /// proof is invalid or doesn't cover the actor.
+    pub fn verify_actor_proof(
+        proof: &Proof,
+        root: &Cid,
+        id: ActorID,
+    ) -> Result<Option<ActorState>> {
+        let store = proof
+            .verify(root)
+            .context("invalid state tree proof")
+            .or_fatal()?;
+        StateTree::new_from_root(store, root)?.get_actor(id)
+    }
+}
+
+/// A change to an actor between two state trees, see [`StateTree::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActorChange {
+    /// The actor was created.
+    Added { key: ActorID, value: ActorState },
+    /// The actor was deleted.
+    Removed { key: ActorID, value: ActorState },
+    /// The actor's state was modified.
+    Modified {
+        key: ActorID,
+        before: ActorState,
+        after: ActorState,
+    },
+}
+
+impl ActorChange {
+    /// Returns the ID of the changed actor.
+    pub fn key(&self) -> &ActorID {
+        match self {
+            ActorChange::Added { key, .. }
+            | ActorChange::Removed { key, .. }
+            | ActorChange::Modified { key, .. } => key,
+        }
+    }
+}
+
 /// State of all actor implementations.
 #[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
 pub struct ActorState {
@@ -667,7 +768,7 @@ mod tests {
     use super::HistoryMap;
     use crate::init_actor;
     use crate::init_actor::INIT_ACTOR_ID;
-    use crate::state_tree::{ActorState, StateTree};
+    use crate::state_tree::{ActorChange, ActorState, StateTree};
 
     lazy_static! {
         pub static ref DUMMY_ACCOUNT_ACTOR_CODE_ID: Cid = Cid::new_v1(
@@ -869,6 +970,84 @@ mod tests {
         assert_eq!(tree.get_actor(actor_id).unwrap(), None);
     }
 
+    #[test]
+    fn diff() {
+        let store = MemoryBlockstore::default();
+        let mut tree = StateTree::new(&store, StateTreeVersion::V5).unwrap();
+
+        let actor = |balance: u64| {
+            ActorState::new(
+                *DUMMY_ACCOUNT_ACTOR_CODE_ID,
+                *DUMMY_ACCOUNT_ACTOR_CODE_ID,
+                TokenAmount::from_atto(balance),
+                1,
+                None,
+            )
+        };
+        for id in 100..200 {
+            tree.set_actor(id, actor(id)).unwrap();
+        }
+        let before_root = tree.flush().unwrap();
+
+        tree.set_actor(100, actor(0)).unwrap();
+        tree.delete_actor(101).unwrap();
+        tree.set_actor(200, actor(200)).unwrap();
+        // Unflushed changes can't be diffed.
+        assert!(tree.diff(&before_root).is_err());
+        let after_root = tree.flush().unwrap();
+
+        let before = StateTree::new_from_root(&store, &before_root).unwrap();
+        let mut changes = before.diff(&after_root).unwrap();
+        changes.sort_by_key(|c| *c.key());
+        assert_eq!(
+            changes,
+            vec![
+                ActorChange::Modified {
+                    key: 100,
+                    before: actor(100),
+                    after: actor(0),
+                },
+                ActorChange::Removed {
+                    key: 101,
+                    value: actor(101),
+                },
+                ActorChange::Added {
+                    key: 200,
+                    value: actor(200),
+                },
+            ]
+        );
+        assert_eq!(tree.diff(&after_root).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn prove_actor() {
+        let store = MemoryBlockstore::default();
+        let mut tree = StateTree::new(&store, StateTreeVersion::V5).unwrap();
+        for id in 100..200 {
+            let actor = ActorState::new_empty(*DUMMY_ACCOUNT_ACTOR_CODE_ID, None);
+            tree.set_actor(id, actor).unwrap();
+        }
+        let root = tree.flush().unwrap();
+
+        let proof = tree.prove_actor(&root, 150).unwrap();
+        assert_eq!(
+            StateTree::verify_actor_proof(&proof, &root, 150).unwrap(),
+            Some(ActorState::new_empty(*DUMMY_ACCOUNT_ACTOR_CODE_ID, None))
+        );
+
+        let proof = tree.prove_actor(&root, 1000).unwrap();
+        assert_eq!(
+            StateTree::verify_actor_proof(&proof, &root, 1000).unwrap(),
+            None
+        );
+
+        // The proof must match the root.
+        tree.delete_actor(151).unwrap();
+        let other_root = tree.flush().unwrap();
+        assert!(StateTree::verify_actor_proof(&proof, &other_root, 1000).is_err());
+    }
+
     #[test]
     fn unsupported_versions() {
         let unsupported = vec![
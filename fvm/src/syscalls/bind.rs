@@ -11,6 +11,7 @@ use super::error::Abort;
 use super::{charge_for_exec, update_gas_available, Context, InvocationData};
 use crate::call_manager::backtrace;
 use crate::kernel::{self, ExecutionError, Kernel, SyscallError};
+use crate::machine::Machine;
 
 /// Binds syscalls to a linker, converting the returned error according to the syscall convention:
 ///
@@ -103,6 +104,14 @@ macro_rules! charge_syscall_gas {
     };
 }
 
+macro_rules! observe_syscall {
+    ($kernel:expr, $module:expr, $name:expr) => {
+        if let Some(observer) = &$kernel.machine().context().observer {
+            observer.on_syscall($module, $name);
+        }
+    };
+}
+
 // Unfortunately, we can't implement this for _all_ functions. So we implement it for functions of up to 6 arguments.
 macro_rules! impl_bind_syscalls {
     ($($t:ident)*) => {
@@ -127,6 +136,7 @@ macro_rules! impl_bind_syscalls {
 
                         let (mut memory, mut data) = memory_and_data(&mut caller);
                         charge_syscall_gas!(data.kernel);
+                        observe_syscall!(data.kernel, module, name);
 
                         let ctx = Context{kernel: &mut data.kernel, memory: &mut memory};
                         let out = syscall(ctx $(, $t)*).into();
@@ -157,6 +167,7 @@ macro_rules! impl_bind_syscalls {
 
                         let (mut memory, mut data) = memory_and_data(&mut caller);
                         charge_syscall_gas!(data.kernel);
+                        observe_syscall!(data.kernel, module, name);
 
                         // We need to check to make sure we can store the return value _before_ we do anything.
                         if (ret as u64) > (memory.len() as u64)
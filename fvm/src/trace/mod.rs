@@ -1,31 +1,122 @@
 // Copyright 2021-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::fmt;
+
+use cid::Cid;
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::address::Address;
 use fvm_shared::econ::TokenAmount;
 use fvm_shared::error::ExitCode;
 use fvm_shared::{ActorID, MethodNum};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::export::human;
+use crate::export::tagged::{self, TaggedEnum};
 use crate::gas::GasCharge;
 use crate::kernel::SyscallError;
 
+mod profile;
+mod tree;
+pub use profile::{GasProfile, GasStats, ProfileKey};
+pub(crate) use tree::CallTreeBuilder;
+pub use tree::{CallResult, CallTrace, CallTree};
+
 /// Execution Trace, only for informational and debugging purposes.
 pub type ExecutionTrace = Vec<ExecutionEvent>;
 
+/// A debug message logged by an actor, along with the context it was logged in.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActorLog {
+    /// The actor that logged the message.
+    pub actor: ActorID,
+    /// The method the actor was invoked with.
+    pub method: MethodNum,
+    /// The invocation the message was logged in, counting from 1 at the start of the message
+    /// execution.
+    pub invocation: u64,
+    /// The nonce of the top-level message being executed.
+    pub nonce: u64,
+    /// The logged message.
+    pub msg: String,
+}
+
+impl fmt::Display for ActorLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[actor={} method={} invocation={} nonce={}] {}",
+            self.actor, self.method, self.invocation, self.nonce, self.msg
+        )
+    }
+}
+
 /// An "event" that happened during execution.
 ///
 /// This is marked as `non_exhaustive` so we can introduce additional event types later.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", remote = "Self")]
 #[non_exhaustive]
 pub enum ExecutionEvent {
     GasCharge(GasCharge),
     Call {
         from: ActorID,
+        #[serde(with = "human")]
         to: Address,
         method: MethodNum,
+        #[serde(with = "human")]
         params: RawBytes,
+        #[serde(with = "human")]
         value: TokenAmount,
     },
-    CallReturn(ExitCode, RawBytes),
+    CallReturn(ExitCode, #[serde(with = "human")] RawBytes),
     CallError(SyscallError),
+    /// An actor opened (read) a block.
+    BlockOpen {
+        actor: ActorID,
+        #[serde(with = "human")]
+        cid: Cid,
+        codec: u64,
+        size: u32,
+    },
+    /// An actor linked (wrote) a block.
+    BlockLink {
+        actor: ActorID,
+        #[serde(with = "human")]
+        cid: Cid,
+        codec: u64,
+        size: u32,
+    },
+    /// An actor changed its state root.
+    SetRoot {
+        actor: ActorID,
+        #[serde(with = "human")]
+        cid: Cid,
+    },
+    /// An event of a type unknown to this version of the FVM, loaded from an exported trace. This
+    /// is never produced during execution.
+    Unknown,
+}
+
+impl Serialize for ExecutionEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ExecutionEvent::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExecutionEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        tagged::deserialize(deserializer)
+    }
+}
+
+impl TaggedEnum for ExecutionEvent {
+    const TAG: &'static str = "type";
+
+    fn unknown() -> Self {
+        ExecutionEvent::Unknown
+    }
+
+    fn deserialize_known<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ExecutionEvent::deserialize(deserializer)
+    }
 }
@@ -0,0 +1,229 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io;
+
+use cid::Cid;
+use fvm_shared::address::Address;
+use fvm_shared::MethodNum;
+use num_traits::Zero;
+
+use super::{CallTrace, CallTree};
+use crate::gas::{Gas, GasCharge};
+
+/// Identifies what gas was charged for, and where.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProfileKey {
+    /// The code CID of the called actor. This is `None` for gas charged outside of any call (in
+    /// which case the method is 0), or if the actor's code couldn't be resolved.
+    pub code: Option<Cid>,
+    /// The invoked method.
+    pub method: MethodNum,
+    /// The name of the gas charge.
+    pub charge: Cow<'static, str>,
+}
+
+/// Aggregated gas charges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GasStats {
+    /// The number of charges.
+    pub count: u64,
+    /// The total compute gas charged.
+    pub compute_gas: Gas,
+    /// The total "other" gas charged.
+    pub other_gas: Gas,
+}
+
+impl GasStats {
+    /// Returns the total gas charged.
+    pub fn total(&self) -> Gas {
+        self.compute_gas + self.other_gas
+    }
+
+    fn add(&mut self, charge: &GasCharge) {
+        self.count += 1;
+        self.compute_gas += charge.compute_gas;
+        self.other_gas += charge.other_gas;
+    }
+}
+
+/// A gas profile aggregated over the call trees of any number of messages.
+///
+/// Gas is aggregated both by [`ProfileKey`] (see [`GasProfile::stats`]) and by call stack (see
+/// [`GasProfile::write_folded`]). The latter attributes gas to the call sites that led to it.
+#[derive(Clone, Debug, Default)]
+pub struct GasProfile {
+    stats: BTreeMap<ProfileKey, GasStats>,
+    stacks: BTreeMap<String, Gas>,
+}
+
+impl GasProfile {
+    /// Creates an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the gas charged by a message to the profile.
+    ///
+    /// Call trees only record the address of each called actor, so `code_of` is used to resolve
+    /// actors to their code CIDs (e.g., by looking them up in the state tree after the message has
+    /// been applied). Calls to actors that can't be resolved are attributed to their address in
+    /// folded stacks.
+    pub fn record(&mut self, tree: &CallTree, mut code_of: impl FnMut(&Address) -> Option<Cid>) {
+        for charge in &tree.gas_charges {
+            let key = ProfileKey {
+                code: None,
+                method: 0,
+                charge: charge.name.clone(),
+            };
+            self.stats.entry(key).or_default().add(charge);
+            *self.stacks.entry(charge.name.to_string()).or_default() += charge.total();
+        }
+
+        let mut stack = Vec::new();
+        for call in &tree.calls {
+            self.record_call(call, &mut code_of, &mut stack);
+        }
+    }
+
+    fn record_call(
+        &mut self,
+        call: &CallTrace,
+        code_of: &mut impl FnMut(&Address) -> Option<Cid>,
+        stack: &mut Vec<String>,
+    ) {
+        let code = code_of(&call.to);
+        let frame = match &code {
+            Some(code) => format!("{}:{}", code, call.method),
+            None => format!("{}:{}", call.to, call.method),
+        };
+        stack.push(frame);
+
+        for charge in &call.gas_charges {
+            let key = ProfileKey {
+                code,
+                method: call.method,
+                charge: charge.name.clone(),
+            };
+            self.stats.entry(key).or_default().add(charge);
+
+            let folded = format!("{};{}", stack.join(";"), charge.name);
+            *self.stacks.entry(folded).or_default() += charge.total();
+        }
+
+        for subcall in &call.subcalls {
+            self.record_call(subcall, code_of, stack);
+        }
+        stack.pop();
+    }
+
+    /// Returns the aggregated gas charges, ordered by key.
+    pub fn stats(&self) -> impl Iterator<Item = (&ProfileKey, &GasStats)> + '_ {
+        self.stats.iter()
+    }
+
+    /// Returns the total gas charged across all recorded messages.
+    pub fn total(&self) -> Gas {
+        self.stats
+            .values()
+            .fold(Gas::zero(), |acc, stats| acc + stats.total())
+    }
+
+    /// Writes the profile in the "folded stacks" format understood by flamegraph tools (e.g.,
+    /// `inferno-flamegraph` or `flamegraph.pl`).
+    ///
+    /// Each line is a semicolon-separated stack of calls (`<code>:<method>`), ending with the name
+    /// of the gas charge, followed by the total gas charged in _milligas_.
+    pub fn write_folded(&self, mut w: impl io::Write) -> io::Result<()> {
+        for (stack, gas) in &self.stacks {
+            if !gas.is_zero() {
+                writeln!(w, "{} {}", stack, gas.as_milligas())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::*;
+    use crate::trace::ExecutionEvent;
+    use crate::EMPTY_ARR_CID;
+
+    fn call(to: u64, method: MethodNum) -> ExecutionEvent {
+        ExecutionEvent::Call {
+            from: 100,
+            to: Address::new_id(to),
+            method,
+            params: RawBytes::default(),
+            value: TokenAmount::default(),
+        }
+    }
+
+    fn charge(name: &'static str, compute: i64, other: i64) -> ExecutionEvent {
+        ExecutionEvent::GasCharge(GasCharge::new(name, Gas::new(compute), Gas::new(other)))
+    }
+
+    fn ret() -> ExecutionEvent {
+        ExecutionEvent::CallReturn(ExitCode::OK, RawBytes::default())
+    }
+
+    #[test]
+    fn profile() {
+        let tree = CallTree::from_flat(&[
+            charge("OnChainMessage", 1, 2),
+            call(1000, 2),
+            charge("OnMethodInvocation", 3, 0),
+            call(1001, 3),
+            charge("OnBlockOpen", 4, 1),
+            ret(),
+            charge("OnBlockOpen", 5, 0),
+            ret(),
+        ])
+        .unwrap();
+
+        let mut profile = GasProfile::new();
+        // Only actor 1000 can be resolved.
+        let code_of = |addr: &Address| (*addr == Address::new_id(1000)).then_some(*EMPTY_ARR_CID);
+        profile.record(&tree, code_of);
+        profile.record(&tree, code_of);
+
+        assert_eq!(profile.total(), Gas::new(2 * 16));
+        let block_open = ProfileKey {
+            code: Some(*EMPTY_ARR_CID),
+            method: 2,
+            charge: "OnBlockOpen".into(),
+        };
+        let stats: BTreeMap<_, _> = profile.stats().collect();
+        assert_eq!(
+            stats[&block_open],
+            &GasStats {
+                count: 2,
+                compute_gas: Gas::new(10),
+                other_gas: Gas::zero(),
+            }
+        );
+
+        let mut folded = Vec::new();
+        profile.write_folded(&mut folded).unwrap();
+        let caller = format!("{}:2", *EMPTY_ARR_CID);
+        let expected = [
+            format!("OnChainMessage {}", 6000),
+            format!("{caller};OnBlockOpen {}", 10000),
+            format!("{caller};OnMethodInvocation {}", 6000),
+            format!("{caller};f01001:3;OnBlockOpen {}", 10000),
+        ];
+        assert_eq!(
+            String::from_utf8(folded)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+}
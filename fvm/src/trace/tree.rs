@@ -0,0 +1,287 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use anyhow::anyhow;
+use cid::Cid;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::event::StampedEvent;
+use fvm_shared::{ActorID, MethodNum};
+use serde::{Deserialize, Serialize};
+
+use super::ExecutionEvent;
+use crate::export::human;
+use crate::gas::GasCharge;
+use crate::kernel::SyscallError;
+
+/// A hierarchical execution trace of a single message, only for informational and debugging
+/// purposes.
+///
+/// Unlike the flat [`ExecutionTrace`](super::ExecutionTrace), each call holds everything that
+/// happened while it was executing, including its sub-calls.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CallTree {
+    /// Gas charged outside of any call (e.g., for message inclusion).
+    pub gas_charges: Vec<GasCharge>,
+    /// The top-level calls, in execution order.
+    pub calls: Vec<CallTrace>,
+}
+
+/// A single call in a [`CallTree`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CallTrace {
+    pub from: ActorID,
+    #[serde(with = "human")]
+    pub to: Address,
+    pub method: MethodNum,
+    #[serde(with = "human")]
+    pub params: RawBytes,
+    #[serde(with = "human")]
+    pub value: TokenAmount,
+    /// Gas charged by this call, excluding the gas charged by its sub-calls.
+    pub gas_charges: Vec<GasCharge>,
+    /// Blocks read by the receiver.
+    #[serde(with = "human::vec")]
+    pub ipld_reads: Vec<Cid>,
+    /// Blocks written by the receiver.
+    #[serde(with = "human::vec")]
+    pub ipld_writes: Vec<Cid>,
+    /// The last state root set by the receiver, if any.
+    #[serde(with = "human::option")]
+    pub state_root: Option<Cid>,
+    /// Events emitted by the receiver. These are recorded when emitted, so they're included even
+    /// if they're later discarded because the call (or one of its callers) failed.
+    pub events: Vec<StampedEvent>,
+    /// Calls made by the receiver, in execution order.
+    pub subcalls: Vec<CallTrace>,
+    /// The result of the call, or `None` if the trace ended before the call returned.
+    pub result: Option<CallResult>,
+}
+
+/// The result of a traced call.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum CallResult {
+    /// The call returned with the given exit code and return value.
+    Return(ExitCode, #[serde(with = "human")] RawBytes),
+    /// The call failed with a syscall error.
+    Error(SyscallError),
+}
+
+impl CallTrace {
+    /// Returns the exit code of the call, if it returned.
+    pub fn exit_code(&self) -> Option<ExitCode> {
+        match &self.result {
+            Some(CallResult::Return(code, _)) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl CallTree {
+    /// Reconstructs a call tree from a flat execution trace.
+    ///
+    /// Flat traces don't record emitted events, so these will be empty. Fails if the trace
+    /// contains a return without a matching call.
+    pub fn from_flat<'a>(
+        trace: impl IntoIterator<Item = &'a ExecutionEvent>,
+    ) -> anyhow::Result<Self> {
+        let mut builder = CallTreeBuilder::default();
+        for event in trace {
+            builder.push(event.clone())?;
+        }
+        Ok(builder.finish())
+    }
+}
+
+/// Incrementally builds a [`CallTree`] from execution events, as they happen.
+#[derive(Default)]
+pub(crate) struct CallTreeBuilder {
+    tree: CallTree,
+    /// The currently executing calls, innermost last.
+    stack: Vec<CallTrace>,
+}
+
+impl CallTreeBuilder {
+    /// Records an execution event, opening or closing calls as necessary.
+    pub fn push(&mut self, event: ExecutionEvent) -> anyhow::Result<()> {
+        match event {
+            ExecutionEvent::GasCharge(charge) => match self.stack.last_mut() {
+                Some(call) => call.gas_charges.push(charge),
+                None => self.tree.gas_charges.push(charge),
+            },
+            ExecutionEvent::Call {
+                from,
+                to,
+                method,
+                params,
+                value,
+            } => self.stack.push(CallTrace {
+                from,
+                to,
+                method,
+                params,
+                value,
+                gas_charges: Vec::new(),
+                ipld_reads: Vec::new(),
+                ipld_writes: Vec::new(),
+                state_root: None,
+                events: Vec::new(),
+                subcalls: Vec::new(),
+                result: None,
+            }),
+            ExecutionEvent::CallReturn(code, data) => {
+                self.end_call(CallResult::Return(code, data))?
+            }
+            ExecutionEvent::CallError(err) => self.end_call(CallResult::Error(err))?,
+            ExecutionEvent::BlockOpen { cid, .. } => {
+                if let Some(call) = self.stack.last_mut() {
+                    call.ipld_reads.push(cid)
+                }
+            }
+            ExecutionEvent::BlockLink { cid, .. } => {
+                if let Some(call) = self.stack.last_mut() {
+                    call.ipld_writes.push(cid)
+                }
+            }
+            ExecutionEvent::SetRoot { cid, .. } => {
+                if let Some(call) = self.stack.last_mut() {
+                    call.state_root = Some(cid)
+                }
+            }
+            ExecutionEvent::Unknown => {}
+        }
+        Ok(())
+    }
+
+    /// Records an event emitted by the currently executing call.
+    pub fn event(&mut self, evt: StampedEvent) {
+        if let Some(call) = self.stack.last_mut() {
+            call.events.push(evt);
+        }
+    }
+
+    /// Finishes the tree, closing any calls that haven't returned.
+    pub fn finish(mut self) -> CallTree {
+        while self.close() {}
+        self.tree
+    }
+
+    fn end_call(&mut self, result: CallResult) -> anyhow::Result<()> {
+        match self.stack.last_mut() {
+            Some(call) => call.result = Some(result),
+            None => return Err(anyhow!("call return without a matching call")),
+        }
+        self.close();
+        Ok(())
+    }
+
+    /// Pops the innermost call and attaches it to its caller. Returns false if there are no open
+    /// calls.
+    fn close(&mut self) -> bool {
+        let call = match self.stack.pop() {
+            Some(call) => call,
+            None => return false,
+        };
+        match self.stack.last_mut() {
+            Some(parent) => parent.subcalls.push(call),
+            None => self.tree.calls.push(call),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::DAG_CBOR;
+    use fvm_shared::error::ErrorNumber;
+    use num_traits::Zero;
+
+    use super::*;
+    use crate::gas::Gas;
+    use crate::EMPTY_ARR_CID;
+
+    fn call(from: ActorID, to: ActorID) -> ExecutionEvent {
+        ExecutionEvent::Call {
+            from,
+            to: Address::new_id(to),
+            method: 1,
+            params: RawBytes::default(),
+            value: TokenAmount::default(),
+        }
+    }
+
+    fn charge(name: &'static str) -> ExecutionEvent {
+        ExecutionEvent::GasCharge(GasCharge::new(name, Gas::new(1), Gas::zero()))
+    }
+
+    fn names(charges: &[GasCharge]) -> Vec<&str> {
+        charges.iter().map(|c| &*c.name).collect()
+    }
+
+    #[test]
+    fn from_flat() {
+        let trace = vec![
+            charge("inclusion"),
+            call(100, 1000),
+            charge("a"),
+            call(1000, 1001),
+            charge("b"),
+            ExecutionEvent::BlockOpen {
+                actor: 1001,
+                cid: *EMPTY_ARR_CID,
+                codec: DAG_CBOR,
+                size: 1,
+            },
+            ExecutionEvent::CallError(SyscallError::new(ErrorNumber::NotFound, "nope")),
+            charge("c"),
+            call(1000, 1002),
+            ExecutionEvent::SetRoot {
+                actor: 1002,
+                cid: *EMPTY_ARR_CID,
+            },
+            ExecutionEvent::CallReturn(ExitCode::USR_FORBIDDEN, RawBytes::new(vec![1])),
+            ExecutionEvent::CallReturn(ExitCode::OK, RawBytes::default()),
+            charge("return"),
+        ];
+
+        let tree = CallTree::from_flat(&trace).unwrap();
+        assert_eq!(names(&tree.gas_charges), ["inclusion", "return"]);
+        assert_eq!(tree.calls.len(), 1);
+
+        let root = &tree.calls[0];
+        assert_eq!(root.to, Address::new_id(1000));
+        assert_eq!(root.exit_code(), Some(ExitCode::OK));
+        assert_eq!(names(&root.gas_charges), ["a", "c"]);
+        assert_eq!(root.subcalls.len(), 2);
+
+        let (first, second) = (&root.subcalls[0], &root.subcalls[1]);
+        assert_eq!(names(&first.gas_charges), ["b"]);
+        assert_eq!(first.ipld_reads, [*EMPTY_ARR_CID]);
+        assert_eq!(first.state_root, None);
+        assert!(matches!(first.result, Some(CallResult::Error(_))));
+        assert_eq!(first.exit_code(), None);
+        assert_eq!(second.to, Address::new_id(1002));
+        assert_eq!(second.exit_code(), Some(ExitCode::USR_FORBIDDEN));
+        assert_eq!(second.state_root, Some(*EMPTY_ARR_CID));
+        assert!(second.ipld_reads.is_empty());
+        assert!(second.subcalls.is_empty());
+    }
+
+    #[test]
+    fn from_flat_unfinished() {
+        let tree = CallTree::from_flat(&[call(100, 1000), call(1000, 1001)]).unwrap();
+        assert_eq!(tree.calls.len(), 1);
+        assert!(tree.calls[0].result.is_none());
+        assert!(tree.calls[0].subcalls[0].result.is_none());
+    }
+
+    #[test]
+    fn from_flat_unbalanced() {
+        let trace = [ExecutionEvent::CallReturn(
+            ExitCode::OK,
+            RawBytes::default(),
+        )];
+        assert!(CallTree::from_flat(&trace).is_err());
+    }
+}
@@ -331,7 +331,7 @@ mod ipld {
 
         // assert gas
         {
-            let price_list = call_manager.machine.context().price_list;
+            let price_list = &call_manager.machine.context().price_list;
             let expected_create_price = price_list.on_block_create(block.len() as usize).total();
             let expected_read_price = price_list.on_block_read(block.len() as usize).total();
 
@@ -397,7 +397,7 @@ mod ipld {
 
         // assert gas
         {
-            let price_list = call_manager.machine.context().price_list;
+            let price_list = &call_manager.machine.context().price_list;
             let expected_create_price = price_list.on_block_create(block.len() as usize).total();
             let expected_stat_price = price_list.on_block_stat().total();
 
@@ -539,7 +539,7 @@ mod gas {
         let expected_list = price_list_by_network_version(STUB_NETWORK_VER);
         assert_eq!(
             kern.price_list(),
-            expected_list,
+            &*expected_list,
             "price list should be the same as the one used in the kernel {}",
             STUB_NETWORK_VER
         );
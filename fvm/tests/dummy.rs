@@ -13,6 +13,7 @@ use fvm::gas::{Gas, GasCharge, GasTimer, GasTracker};
 use fvm::machine::limiter::MemoryLimiter;
 use fvm::machine::{Machine, MachineContext, Manifest, NetworkConfig};
 use fvm::state_tree::{ActorState, StateTree};
+use fvm::trace::{ActorLog, ExecutionEvent};
 use fvm::{kernel, Kernel};
 use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
 use fvm_ipld_encoding::{CborStore, DAG_CBOR};
@@ -326,7 +327,9 @@ impl CallManager for DummyCallManager {
                     cause: None,
                 },
                 exec_trace: Vec::new(),
+                call_tree: Default::default(),
                 events: Vec::new(),
+                logs: Vec::new(),
             },
             self.machine,
         )
@@ -379,14 +382,18 @@ impl CallManager for DummyCallManager {
     }
 
     fn invocation_count(&self) -> u64 {
-        todo!()
+        0
     }
 
     fn limiter_mut(&mut self) -> &mut <Self::Machine as Machine>::Limiter {
         &mut self.limits
     }
 
+    fn trace(&mut self, _event: ExecutionEvent) {}
+
     fn append_event(&mut self, _evt: StampedEvent) {
         todo!()
     }
+
+    fn append_log(&mut self, _log: ActorLog) {}
 }
@@ -2,10 +2,12 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::ops::Range;
+
 use anyhow::anyhow;
 use cid::multihash::Code;
 use cid::Cid;
-use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore, Proof, ProofRecorder};
 use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::ser::Serialize;
 use fvm_ipld_encoding::serde::Deserialize;
@@ -13,6 +15,7 @@ use fvm_ipld_encoding::CborStore;
 use itertools::sorted;
 
 use super::ValueMut;
+use crate::diff::{Change, Differ};
 use crate::node::{CollapsedNode, Link};
 use crate::root::version::{Version as AmtVersion, V0, V3};
 use crate::root::RootImpl;
@@ -331,6 +334,93 @@ where
         Ok(cid)
     }
 
+    /// Computes the changes from this AMT to `other`, which may live in a different store. Changes
+    /// are returned in index order.
+    ///
+    /// Both AMTs are walked in parallel and sub-trees with equal CIDs are skipped, so the cost is
+    /// proportional to the size of the change rather than the size of the arrays. Both AMTs must
+    /// use the same bit width, but may differ in height.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fvm_ipld_amt::{Amt, Change};
+    ///
+    /// let store = fvm_ipld_blockstore::MemoryBlockstore::default();
+    ///
+    /// let mut before: Amt<String, _> = Amt::new(&store);
+    /// before.set(1, "One".to_owned()).unwrap();
+    /// before.set(2, "Two".to_owned()).unwrap();
+    ///
+    /// let mut after: Amt<String, _> = Amt::new(&store);
+    /// after.set(2, "Deux".to_owned()).unwrap();
+    /// after.set(100, "Cent".to_owned()).unwrap();
+    ///
+    /// assert_eq!(
+    ///     before.diff(&after).unwrap(),
+    ///     vec![
+    ///         Change::Removed { index: 1, value: "One".to_owned() },
+    ///         Change::Modified { index: 2, before: "Two".to_owned(), after: "Deux".to_owned() },
+    ///         Change::Added { index: 100, value: "Cent".to_owned() },
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff<BS2>(&self, other: &AmtImpl<V, BS2, Ver>) -> Result<Vec<Change<V>>, Error>
+    where
+        V: PartialEq + Clone,
+        BS2: Blockstore,
+    {
+        if self.bit_width() != other.bit_width() {
+            return Err(anyhow!("cannot diff AMTs with different bit widths").into());
+        }
+        let mut changes = Vec::new();
+        if self.flushed_cid.is_some() && self.flushed_cid == other.flushed_cid {
+            return Ok(changes);
+        }
+        let differ = Differ {
+            bit_width: self.bit_width(),
+            before_store: &self.block_store,
+            after_store: &other.block_store,
+        };
+        differ.diff_roots(
+            &self.root.node,
+            self.height(),
+            &other.root.node,
+            other.height(),
+            &mut changes,
+        )?;
+        Ok(changes)
+    }
+
+    /// Generates a proof of the value at index `i` (or of its absence) in this Amt, which must
+    /// have been flushed. The proof is made up of the blocks on the path to `i`, and can be checked
+    /// with [`Amt::verify_proof`](AmtImpl::verify_proof).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fvm_ipld_amt::Amt;
+    ///
+    /// let store = fvm_ipld_blockstore::MemoryBlockstore::default();
+    ///
+    /// let mut amt: Amt<u64, _> = Amt::new(&store);
+    /// amt.batch_set(0..100).unwrap();
+    /// let root = amt.flush().unwrap();
+    ///
+    /// let proof = amt.prove(42).unwrap();
+    /// assert_eq!(Amt::<u64, _>::verify_proof(&proof, &root, 42).unwrap(), Some(42));
+    /// let proof = amt.prove(1000).unwrap();
+    /// assert_eq!(Amt::<u64, _>::verify_proof(&proof, &root, 1000).unwrap(), None);
+    /// ```
+    pub fn prove(&self, i: u64) -> Result<Proof, Error> {
+        let root = self
+            .flushed_cid
+            .ok_or("AMT must be flushed before generating a proof")?;
+        let recorder = ProofRecorder::new(&self.block_store);
+        AmtImpl::<V, _, Ver>::load(&root, &recorder)?.get(i)?;
+        Ok(recorder.into_proof(root))
+    }
+
     /// Iterates over each value in the Amt and runs a function on the values.
     ///
     /// The index in the amt is a `u64` and the value is the generic parameter `V` as defined
@@ -367,10 +457,110 @@ where
 
     /// Iterates over each value in the Amt and runs a function on the values, for as long as that
     /// function keeps returning `true`.
-    pub fn for_each_while<F>(&self, mut f: F) -> Result<(), Error>
+    pub fn for_each_while<F>(&self, f: F) -> Result<(), Error>
     where
         F: FnMut(u64, &V) -> anyhow::Result<bool>,
     {
+        self.for_each_while_from(0, f)
+    }
+
+    /// Iterates over the values with indices in `range`, in order, and runs a function on them.
+    /// The Amt is traversed straight to the start of the range, without loading the nodes before
+    /// it.
+    ///
+    /// Returns the index of the first value after the range (if any) to continue from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fvm_ipld_amt::Amt;
+    ///
+    /// let store = fvm_ipld_blockstore::MemoryBlockstore::default();
+    ///
+    /// let mut map: Amt<u64, _> = Amt::new(&store);
+    /// for i in [1, 4, 9, 16, 25] {
+    ///     map.set(i, i * 10).unwrap();
+    /// }
+    ///
+    /// let mut values = Vec::new();
+    /// let next = map
+    ///     .range(2..16, |i, v| {
+    ///         values.push((i, *v));
+    ///         Ok(())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(&values, &[(4, 40), (9, 90)]);
+    /// assert_eq!(next, Some(16));
+    /// ```
+    pub fn range<F>(&self, range: Range<u64>, mut f: F) -> Result<Option<u64>, Error>
+    where
+        F: FnMut(u64, &V) -> anyhow::Result<()>,
+    {
+        let mut next = None;
+        self.for_each_while_from(range.start, |i, v| {
+            if i >= range.end {
+                next = Some(i);
+                return Ok(false);
+            }
+            f(i, v)?;
+            Ok(true)
+        })?;
+        Ok(next)
+    }
+
+    /// Iterates over (at most) `limit` values, in order, starting at index `start`, and runs a
+    /// function on them. The Amt is traversed straight to `start`, without loading the nodes
+    /// before it.
+    ///
+    /// Returns the index of the next value (if any) to resume from, which makes it easy to page
+    /// through the Amt:
+    ///
+    /// ```
+    /// use fvm_ipld_amt::Amt;
+    ///
+    /// let store = fvm_ipld_blockstore::MemoryBlockstore::default();
+    ///
+    /// let mut map: Amt<u64, _> = Amt::new(&store);
+    /// map.batch_set(0..100).unwrap();
+    ///
+    /// let mut pages = 0;
+    /// let mut next = Some(0);
+    /// while let Some(start) = next {
+    ///     next = map.for_each_from(start, 30, |_, _| Ok(())).unwrap();
+    ///     pages += 1;
+    /// }
+    /// assert_eq!(pages, 4);
+    /// ```
+    pub fn for_each_from<F>(&self, start: u64, limit: u64, mut f: F) -> Result<Option<u64>, Error>
+    where
+        F: FnMut(u64, &V) -> anyhow::Result<()>,
+    {
+        let mut count = 0;
+        let mut next = None;
+        self.for_each_while_from(start, |i, v| {
+            if count == limit {
+                next = Some(i);
+                return Ok(false);
+            }
+            count += 1;
+            f(i, v)?;
+            Ok(true)
+        })?;
+        Ok(next)
+    }
+
+    fn for_each_while_from<F>(&self, start: u64, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(u64, &V) -> anyhow::Result<bool>,
+    {
+        if start > MAX_INDEX {
+            return Err(Error::OutOfRange(start));
+        }
+
+        if start >= nodes_for_height(self.bit_width(), self.height() + 1) {
+            return Ok(());
+        }
+
         self.root
             .node
             .for_each_while(
@@ -378,6 +568,7 @@ where
                 self.height(),
                 self.bit_width(),
                 0,
+                start,
                 &mut f,
             )
             .map(|_| ())
@@ -460,3 +651,17 @@ where
         }
     }
 }
+
+impl<V, Ver> AmtImpl<V, MemoryBlockstore, Ver>
+where
+    V: DeserializeOwned + Serialize + Clone,
+    Ver: AmtVersion,
+{
+    /// Verifies a proof generated by [`Amt::prove`](AmtImpl::prove) against the trusted Amt root
+    /// `root`, and returns the value at index `i` (or `None` if there's none). Fails if the proof
+    /// is invalid or doesn't cover `i`.
+    pub fn verify_proof(proof: &Proof, root: &Cid, i: u64) -> Result<Option<V>, Error> {
+        let store = proof.verify(root)?;
+        Ok(Self::load(root, store)?.get(i)?.cloned())
+    }
+}
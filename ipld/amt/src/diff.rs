@@ -0,0 +1,253 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::cmp::Ordering;
+
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::node::{CollapsedNode, Link};
+use crate::{nodes_for_height, Error, Node};
+
+/// A change to a single index between two versions of an array, see
+/// [`Amt::diff`](crate::Amt::diff).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<V> {
+    /// The index was set.
+    Added { index: u64, value: V },
+    /// The index was deleted.
+    Removed { index: u64, value: V },
+    /// The value at the index was modified.
+    Modified { index: u64, before: V, after: V },
+}
+
+impl<V> Change<V> {
+    /// Returns the changed index.
+    pub fn index(&self) -> u64 {
+        match self {
+            Change::Added { index, .. }
+            | Change::Removed { index, .. }
+            | Change::Modified { index, .. } => *index,
+        }
+    }
+}
+
+/// Walks two nodes in parallel, skipping any child links with equal CIDs.
+pub(crate) struct Differ<'a, BS1, BS2> {
+    pub bit_width: u32,
+    pub before_store: &'a BS1,
+    pub after_store: &'a BS2,
+}
+
+impl<'a, BS1, BS2> Differ<'a, BS1, BS2>
+where
+    BS1: Blockstore,
+    BS2: Blockstore,
+{
+    /// Diffs two root nodes of (possibly) different heights.
+    pub fn diff_roots<V>(
+        &self,
+        before: &Node<V>,
+        before_height: u32,
+        after: &Node<V>,
+        after_height: u32,
+        changes: &mut Vec<Change<V>>,
+    ) -> Result<(), Error>
+    where
+        V: Serialize + DeserializeOwned + PartialEq + Clone,
+    {
+        // The root of the shorter tree corresponds to the first sub-tree of the taller one, and
+        // everything else in the taller tree is either new or gone.
+        match before_height.cmp(&after_height) {
+            Ordering::Greater => {
+                let links = match before {
+                    Node::Link { links } => links,
+                    Node::Leaf { .. } => unreachable!("non-zero height cannot be a leaf node"),
+                };
+                match &links[0] {
+                    Some(link) => {
+                        let node = load_link(link, self.before_store, self.bit_width)?;
+                        self.diff_roots(node, before_height - 1, after, after_height, changes)?
+                    }
+                    None => {
+                        self.collect(after, self.after_store, after_height, 0, changes, added)?
+                    }
+                }
+                let nfh = nodes_for_height(self.bit_width, before_height);
+                for (i, link) in (0..).zip(links.iter()).skip(1) {
+                    if let Some(link) = link {
+                        let node = load_link(link, self.before_store, self.bit_width)?;
+                        let offset = i * nfh;
+                        self.collect(
+                            node,
+                            self.before_store,
+                            before_height - 1,
+                            offset,
+                            changes,
+                            removed,
+                        )?;
+                    }
+                }
+                Ok(())
+            }
+            Ordering::Less => {
+                let links = match after {
+                    Node::Link { links } => links,
+                    Node::Leaf { .. } => unreachable!("non-zero height cannot be a leaf node"),
+                };
+                match &links[0] {
+                    Some(link) => {
+                        let node = load_link(link, self.after_store, self.bit_width)?;
+                        self.diff_roots(before, before_height, node, after_height - 1, changes)?
+                    }
+                    None => self.collect(
+                        before,
+                        self.before_store,
+                        before_height,
+                        0,
+                        changes,
+                        removed,
+                    )?,
+                }
+                let nfh = nodes_for_height(self.bit_width, after_height);
+                for (i, link) in (0..).zip(links.iter()).skip(1) {
+                    if let Some(link) = link {
+                        let node = load_link(link, self.after_store, self.bit_width)?;
+                        let offset = i * nfh;
+                        self.collect(
+                            node,
+                            self.after_store,
+                            after_height - 1,
+                            offset,
+                            changes,
+                            added,
+                        )?;
+                    }
+                }
+                Ok(())
+            }
+            Ordering::Equal => self.diff_nodes(before, after, before_height, 0, changes),
+        }
+    }
+
+    /// Diffs two nodes at the same height and offset.
+    fn diff_nodes<V>(
+        &self,
+        before: &Node<V>,
+        after: &Node<V>,
+        height: u32,
+        offset: u64,
+        changes: &mut Vec<Change<V>>,
+    ) -> Result<(), Error>
+    where
+        V: Serialize + DeserializeOwned + PartialEq + Clone,
+    {
+        match (before, after) {
+            (Node::Leaf { vals: before }, Node::Leaf { vals: after }) => {
+                for (i, (a, b)) in (0..).zip(before.iter().zip(after)) {
+                    let index = offset + i;
+                    match (a, b) {
+                        (Some(a), Some(b)) if a != b => changes.push(Change::Modified {
+                            index,
+                            before: a.clone(),
+                            after: b.clone(),
+                        }),
+                        (Some(a), None) => changes.push(removed(index, a)),
+                        (None, Some(b)) => changes.push(added(index, b)),
+                        _ => {}
+                    }
+                }
+            }
+            (Node::Link { links: before }, Node::Link { links: after }) => {
+                let nfh = nodes_for_height(self.bit_width, height);
+                for (i, (a, b)) in (0..).zip(before.iter().zip(after)) {
+                    let offset = offset + i * nfh;
+                    match (a, b) {
+                        (None, None) => {}
+                        (Some(Link::Cid { cid: a, .. }), Some(Link::Cid { cid: b, .. }))
+                            if a == b => {}
+                        (Some(a), Some(b)) => {
+                            let a = load_link(a, self.before_store, self.bit_width)?;
+                            let b = load_link(b, self.after_store, self.bit_width)?;
+                            self.diff_nodes(a, b, height - 1, offset, changes)?;
+                        }
+                        (Some(a), None) => {
+                            let a = load_link(a, self.before_store, self.bit_width)?;
+                            self.collect(
+                                a,
+                                self.before_store,
+                                height - 1,
+                                offset,
+                                changes,
+                                removed,
+                            )?;
+                        }
+                        (None, Some(b)) => {
+                            let b = load_link(b, self.after_store, self.bit_width)?;
+                            self.collect(b, self.after_store, height - 1, offset, changes, added)?;
+                        }
+                    }
+                }
+            }
+            _ => return Err(anyhow::anyhow!("AMT nodes at the same height differ in kind").into()),
+        }
+        Ok(())
+    }
+
+    /// Records every value under a node as a change.
+    fn collect<V, BS>(
+        &self,
+        node: &Node<V>,
+        store: &BS,
+        height: u32,
+        offset: u64,
+        changes: &mut Vec<Change<V>>,
+        change: fn(u64, &V) -> Change<V>,
+    ) -> Result<(), Error>
+    where
+        V: Serialize + DeserializeOwned,
+        BS: Blockstore,
+    {
+        node.for_each_while(store, height, self.bit_width, offset, 0, &mut |i, v| {
+            changes.push(change(i, v));
+            Ok(true)
+        })?;
+        Ok(())
+    }
+}
+
+fn added<V: Clone>(index: u64, value: &V) -> Change<V> {
+    Change::Added {
+        index,
+        value: value.clone(),
+    }
+}
+
+fn removed<V: Clone>(index: u64, value: &V) -> Change<V> {
+    Change::Removed {
+        index,
+        value: value.clone(),
+    }
+}
+
+/// Returns the node a link points to, loading and caching it if necessary.
+fn load_link<'a, V, BS>(link: &'a Link<V>, store: &BS, bit_width: u32) -> Result<&'a Node<V>, Error>
+where
+    V: DeserializeOwned,
+    BS: Blockstore,
+{
+    match link {
+        Link::Cid { cid, cache } => cache
+            .get_or_try_init(|| {
+                store
+                    .get_cbor::<CollapsedNode<V>>(cid)?
+                    .ok_or_else(|| Error::CidNotFound(cid.to_string()))?
+                    .expand(bit_width)
+                    .map(Box::new)
+            })
+            .map(|node| &**node),
+        Link::Dirty(node) => Ok(node),
+    }
+}
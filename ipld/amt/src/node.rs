@@ -420,12 +420,16 @@ where
         }
     }
 
+    /// Calls `f` on each value with an index of at least `start`, for as long as it keeps
+    /// returning `true`. Sub-trees that only contain indices below `start` are skipped without
+    /// being loaded.
     pub(super) fn for_each_while<S, F>(
         &self,
         bs: &S,
         height: u32,
         bit_width: u32,
         offset: u64,
+        start: u64,
         f: &mut F,
     ) -> Result<bool, Error>
     where
@@ -435,6 +439,9 @@ where
         match self {
             Node::Leaf { vals } => {
                 for (i, v) in (0..).zip(vals.iter()) {
+                    if offset + i < start {
+                        continue;
+                    }
                     if let Some(v) = v {
                         let keep_going = f(offset + i, v)?;
 
@@ -445,12 +452,18 @@ where
                 }
             }
             Node::Link { links } => {
+                let nfh = nodes_for_height(bit_width, height);
                 for (i, l) in (0..).zip(links.iter()) {
+                    // Only compute the offset of links that are present: the offsets of the
+                    // trailing empty slots of a node may not fit in a u64.
                     if let Some(l) = l {
-                        let offs = offset + (i * nodes_for_height(bit_width, height));
+                        let offs = offset + (i * nfh);
+                        if offs.saturating_add(nfh) <= start {
+                            continue;
+                        }
                         let keep_going = match l {
                             Link::Dirty(sub) => {
-                                sub.for_each_while(bs, height - 1, bit_width, offs, f)?
+                                sub.for_each_while(bs, height - 1, bit_width, offs, start, f)?
                             }
                             Link::Cid { cid, cache } => {
                                 let cached_node = cache.get_or_try_init(|| {
@@ -460,7 +473,14 @@ where
                                         .map(Box::new)
                                 })?;
 
-                                cached_node.for_each_while(bs, height - 1, bit_width, offs, f)?
+                                cached_node.for_each_while(
+                                    bs,
+                                    height - 1,
+                                    bit_width,
+                                    offs,
+                                    start,
+                                    f,
+                                )?
                             }
                         };
 
@@ -2,11 +2,12 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 
-use fvm_ipld_amt::{Amt, Amtv0, Error, MAX_INDEX};
+use fvm_ipld_amt::{Amt, Amtv0, Change, Error, MAX_INDEX};
 use fvm_ipld_blockstore::tracking::{BSStats, TrackingBlockstore};
-use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore, Proof};
 use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::ser::Serialize;
 use fvm_ipld_encoding::BytesDe;
@@ -48,7 +49,7 @@ fn basic_get_set() {
         "bafy2bzacedv5uu5za6oqtnozjvju5lhbgaybayzhw4txiojw7hd47ktgbv5wc"
     );
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 1, br: 13, bw: 13});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 1, br: 13, bw: 13, ..Default::default()});
 }
 
 #[test]
@@ -72,7 +73,7 @@ fn legacy_amtv0_basic_get_set() {
         "bafy2bzaceansvim5z2rzifilsbzsjuoul2adx7iad7x3b4paj3qsexqf6ovxk"
     );
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 1, br: 12, bw: 12});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 1, br: 12, bw: 12, ..Default::default()});
 }
 
 #[test]
@@ -99,7 +100,7 @@ fn out_of_range() {
         "bafy2bzacecl3zuubhdvkojg6uhbu4mebaehx554q6algfjitqiivvnrqprkxo"
     );
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 0, w: 22, br: 0, bw: 1039});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 0, w: 22, br: 0, bw: 1039, ..Default::default()});
 }
 
 #[test]
@@ -132,7 +133,7 @@ fn expand() {
     );
 
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 6, w: 6, br: 261, bw: 261});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 6, w: 6, br: 261, bw: 261, ..Default::default()});
 }
 
 #[test]
@@ -167,7 +168,7 @@ fn bulk_insert() {
     );
 
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 717, w: 717, br: 94379, bw: 94379});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 717, w: 717, br: 94379, bw: 94379, ..Default::default()});
 }
 
 #[test]
@@ -195,7 +196,7 @@ fn flush_read() {
     }
 
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 0, w: 16, br: 0, bw: 1930});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 0, w: 16, br: 0, bw: 1930, ..Default::default()});
 }
 
 #[test]
@@ -243,7 +244,7 @@ fn delete() {
         "bafy2bzacebnnxpurpb3zqqr22i7ch4uruz6hgykn3ryzoo4hh3ox2m2kufsvg"
     );
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 4, br: 52, bw: 122});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 4, br: 52, bw: 122, ..Default::default()});
 }
 
 #[test]
@@ -292,7 +293,7 @@ fn delete_first_entry() {
     );
 
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 1, br: 13, bw: 13});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 1, w: 1, br: 13, bw: 13, ..Default::default()});
 }
 
 #[test]
@@ -323,7 +324,7 @@ fn delete_reduce_height() {
         "bafy2bzacebmkyah6kppbszluix3g332hntzx6wfdcqcr5hjdsaxri5jhgrdmo"
     );
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 3, w: 5, br: 117, bw: 147});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 3, w: 5, br: 117, bw: 147, ..Default::default()});
 }
 
 #[test]
@@ -390,7 +391,117 @@ fn for_each() {
     );
 
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 1431, w: 1431, br: 88649, bw: 88649});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 1431, w: 1431, br: 88649, bw: 88649, ..Default::default()});
+}
+
+#[test]
+fn for_each_from() {
+    let mem = MemoryBlockstore::default();
+    let db = TrackingBlockstore::new(&mem);
+    let mut a = Amt::new(&db);
+
+    let indexes: Vec<u64> = (0..10000).filter(|i| (i + 1) % 3 == 0).collect();
+    for i in indexes.iter() {
+        a.set(*i, *i).unwrap();
+    }
+    let c = a.flush().unwrap();
+
+    // Page through the Amt, before and after flushing.
+    for a in [a, Amt::load(&c, &db).unwrap()] {
+        let mut visited = Vec::new();
+        let mut next = Some(0);
+        while let Some(start) = next {
+            let len = visited.len();
+            next = a
+                .for_each_from(start, 7, |i, v| {
+                    assert_eq!(i, *v);
+                    visited.push(i);
+                    Ok(())
+                })
+                .unwrap();
+            assert!(visited.len() - len <= 7);
+        }
+        assert_eq!(visited, indexes);
+    }
+
+    // Starting in the middle only loads the nodes on the way to the start, plus the two leaves
+    // after it (the values span two leaves, and the next index is in a third).
+    let a: Amt<u64, _> = Amt::load(&c, &db).unwrap();
+    let reads = db.stats.borrow().r;
+    let mut visited = Vec::new();
+    let next = a
+        .for_each_from(9000, 5, |i, _| {
+            visited.push(i);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(visited, &[9002, 9005, 9008, 9011, 9014]);
+    assert_eq!(next, Some(9017));
+    assert_eq!(db.stats.borrow().r - reads, a.height() as usize + 2);
+
+    // Starting after the last index or with a limit of 0.
+    assert_eq!(a.for_each_from(9999, 5, |_, _| panic!()).unwrap(), None);
+    assert_eq!(a.for_each_from(1 << 40, 5, |_, _| panic!()).unwrap(), None);
+    assert_eq!(a.for_each_from(3, 0, |_, _| panic!()).unwrap(), Some(5));
+    assert!(matches!(
+        a.for_each_from(MAX_INDEX + 1, 5, |_, _| panic!()),
+        Err(Error::OutOfRange(_))
+    ));
+}
+
+#[test]
+fn range() {
+    let db = MemoryBlockstore::default();
+    let mut a = Amt::new_with_bit_width(&db, 2);
+
+    let indexes: Vec<u64> = (0..1000).filter(|i| i % 7 == 0).collect();
+    for i in indexes.iter() {
+        a.set(*i, *i).unwrap();
+    }
+    let c = a.flush().unwrap();
+    let a: Amt<u64, _> = Amt::load(&c, &db).unwrap();
+
+    for (start, end) in [(0, 1000), (0, 0), (5, 6), (7, 8), (100, 500), (994, 2000)] {
+        let mut visited = Vec::new();
+        let next = a
+            .range(start..end, |i, v| {
+                assert_eq!(i, *v);
+                visited.push(i);
+                Ok(())
+            })
+            .unwrap();
+        let expected: Vec<u64> = indexes
+            .iter()
+            .copied()
+            .filter(|i| (start..end).contains(i))
+            .collect();
+        assert_eq!(visited, expected);
+        assert_eq!(next, indexes.iter().copied().find(|i| *i >= end.max(start)));
+    }
+}
+
+#[test]
+fn range_max_index() {
+    let db = MemoryBlockstore::default();
+    let mut a = Amt::new(&db);
+    a.set(MAX_INDEX, 1u64).unwrap();
+    let c = a.flush().unwrap();
+    let a: Amt<u64, _> = Amt::load(&c, &db).unwrap();
+
+    for start in [0, 1 << 63, MAX_INDEX - 1, MAX_INDEX] {
+        let mut visited = Vec::new();
+        let next = a
+            .for_each_from(start, 10, |i, _| {
+                visited.push(i);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(visited, [MAX_INDEX]);
+        assert_eq!(next, None);
+    }
+
+    let next = a.range(0..MAX_INDEX, |_, _| panic!()).unwrap();
+    assert_eq!(next, Some(MAX_INDEX));
 }
 
 #[test]
@@ -428,7 +539,7 @@ fn for_each_mutate() {
     );
 
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r: 12, w: 12, br: 573, bw: 573});
+    assert_eq!(*db.stats.borrow(), BSStats {r: 12, w: 12, br: 573, bw: 573, ..Default::default()});
 }
 
 #[test]
@@ -451,7 +562,169 @@ fn delete_bug_test() {
     );
     assert_eq!(c, empty_cid);
     #[rustfmt::skip]
-    assert_eq!(*db.stats.borrow(), BSStats {r:0, w:2, br:0, bw:18});
+    assert_eq!(*db.stats.borrow(), BSStats {r:0, w:2, br:0, bw:18, ..Default::default()});
+}
+
+/// Diffs two AMTs by iterating over both in full.
+fn naive_diff<BS1, BS2>(before: &Amt<u64, BS1>, after: &Amt<u64, BS2>) -> Vec<Change<u64>>
+where
+    BS1: Blockstore,
+    BS2: Blockstore,
+{
+    let mut before_vals = BTreeMap::new();
+    before
+        .for_each(|i, v| {
+            before_vals.insert(i, *v);
+            Ok(())
+        })
+        .unwrap();
+    let mut changes = Vec::new();
+    after
+        .for_each(|index, &value| {
+            match before_vals.remove(&index) {
+                None => changes.push(Change::Added { index, value }),
+                Some(before) if before != value => changes.push(Change::Modified {
+                    index,
+                    before,
+                    after: value,
+                }),
+                Some(_) => {}
+            }
+            Ok(())
+        })
+        .unwrap();
+    changes.extend(
+        before_vals
+            .into_iter()
+            .map(|(index, value)| Change::Removed { index, value }),
+    );
+    changes.sort_by_key(|c| c.index());
+    changes
+}
+
+#[test]
+fn diff() {
+    let mem = MemoryBlockstore::default();
+    let db = TrackingBlockstore::new(&mem);
+
+    let mut a = Amt::new(&db);
+    for i in 0..1000 {
+        a.set(i, i).unwrap();
+    }
+    let before_cid = a.flush().unwrap();
+
+    a.set(10, 0).unwrap();
+    a.delete(500).unwrap();
+    // Grow the AMT.
+    a.set(100_000, 100_000).unwrap();
+    let expected = vec![
+        Change::Modified {
+            index: 10,
+            before: 10,
+            after: 0,
+        },
+        Change::Removed {
+            index: 500,
+            value: 500,
+        },
+        Change::Added {
+            index: 100_000,
+            value: 100_000,
+        },
+    ];
+
+    // Diff against a dirty AMT.
+    let before: Amt<u64, _> = Amt::load(&before_cid, &db).unwrap();
+    assert_eq!(before.diff(&a).unwrap(), expected);
+
+    // Diff two flushed AMTs of different heights, skipping unchanged sub-trees.
+    let after_cid = a.flush().unwrap();
+    let reads = db.stats.borrow().r;
+    let before: Amt<u64, _> = Amt::load(&before_cid, &db).unwrap();
+    let after: Amt<u64, _> = Amt::load(&after_cid, &db).unwrap();
+    assert_ne!(before.height(), after.height());
+    assert_eq!(before.diff(&after).unwrap(), expected);
+    // Each AMT has over 100 nodes, but we only need to load the paths to the changes.
+    assert!(db.stats.borrow().r - reads < 20);
+
+    assert_eq!(after.diff(&before).unwrap(), naive_diff(&after, &before));
+    assert_eq!(after.diff(&after).unwrap(), vec![]);
+}
+
+#[test]
+fn diff_random() {
+    let db = MemoryBlockstore::default();
+
+    // A simple LCG so that the test is deterministic.
+    let mut seed = 42u64;
+    let mut rand = move |n: u64| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) % n
+    };
+
+    for _ in 0..50 {
+        let mut a = Amt::new_with_bit_width(&db, 2);
+        let mut b = Amt::new_with_bit_width(&db, 2);
+        let max_index = [10, 100, 10_000][rand(3) as usize];
+        for _ in 0..rand(200) {
+            let (i, v) = (rand(max_index), rand(4));
+            a.set(i, v).unwrap();
+            // Start with mostly the same values.
+            if rand(10) != 0 {
+                b.set(i, v).unwrap();
+            }
+        }
+        for _ in 0..rand(20) {
+            b.set(rand(max_index), rand(4)).unwrap();
+            b.delete(rand(max_index)).unwrap();
+        }
+        a.flush().unwrap();
+        b.flush().unwrap();
+
+        assert_eq!(a.diff(&b).unwrap(), naive_diff(&a, &b));
+        assert_eq!(b.diff(&a).unwrap(), naive_diff(&b, &a));
+    }
+}
+
+#[test]
+fn proof() {
+    let db = MemoryBlockstore::default();
+    let mut a = Amt::new_with_bit_width(&db, 2);
+    let indexes: Vec<u64> = (0..1000).filter(|i| i % 3 == 0).collect();
+    for i in indexes.iter() {
+        a.set(*i, *i).unwrap();
+    }
+    // Proofs can only be generated from flushed state.
+    assert!(a.prove(3).is_err());
+    let root = a.flush().unwrap();
+
+    for i in 0..1100 {
+        let proof = a.prove(i).unwrap();
+        let expected = indexes.contains(&i).then_some(i);
+        assert_eq!(
+            Amt::<u64, _>::verify_proof(&proof, &root, i).unwrap(),
+            expected
+        );
+        // Only the blocks on the path to the index are included.
+        assert!(proof.blocks().len() <= a.height() as usize + 1);
+        if i >= 1024 {
+            // Indices beyond the AMT's capacity are proven absent by the root alone.
+            assert_eq!(proof.blocks().len(), 1);
+        }
+    }
+
+    // A proof doesn't cover indices in other leaves.
+    let proof = a.prove(0).unwrap();
+    assert!(Amt::<u64, _>::verify_proof(&proof, &root, 999).is_err());
+
+    // Proofs are checked against the root and the blocks against their CIDs.
+    let other_root = Amt::<u64, _>::new(&db).flush().unwrap();
+    assert!(Amt::<u64, _>::verify_proof(&proof, &other_root, 0).is_err());
+    let mut blocks = proof.into_blocks();
+    blocks.last_mut().unwrap().1[0] ^= 1;
+    assert!(Amt::<u64, _>::verify_proof(&Proof::new(root, blocks), &root, 0).is_err());
 }
 
 fn tbytes(bz: &[u8]) -> BytesDe {
@@ -0,0 +1,272 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use cid::multihash::{self, Code};
+use cid::Cid;
+
+use super::tracking::BSStats;
+use super::{Block, Blockstore};
+
+/// The identity multihash code.
+const IDENTITY: u64 = 0x0;
+
+/// Wrapper around `Blockstore` that caches recently read blocks, evicting the least recently used
+/// blocks once their total size exceeds a limit.
+///
+/// Only reads populate the cache, and blocks with identity CIDs are never cached (their data is
+/// in the CID). Reads and writes that reach the wrapped store are counted in the `stats`, along
+/// with cache hits and misses.
+#[derive(Debug)]
+pub struct CachingBlockstore<BS> {
+    base: BS,
+    cache: RefCell<Cache>,
+    pub stats: RefCell<BSStats>,
+}
+
+#[derive(Debug)]
+struct Cache {
+    /// Cached blocks along with when they were last used.
+    blocks: HashMap<Cid, (Vec<u8>, u64)>,
+    /// Cached blocks by when they were last used.
+    lru: BTreeMap<u64, Cid>,
+    bytes: usize,
+    max_bytes: usize,
+    tick: u64,
+}
+
+impl<BS> CachingBlockstore<BS>
+where
+    BS: Blockstore,
+{
+    /// Wraps `base`, caching up to `max_bytes` of blocks.
+    pub fn new(base: BS, max_bytes: usize) -> Self {
+        Self {
+            base,
+            cache: RefCell::new(Cache {
+                blocks: HashMap::new(),
+                lru: BTreeMap::new(),
+                bytes: 0,
+                max_bytes,
+                tick: 0,
+            }),
+            stats: Default::default(),
+        }
+    }
+
+    /// Returns the total size of the cached blocks, in bytes.
+    pub fn cached_bytes(&self) -> usize {
+        self.cache.borrow().bytes
+    }
+}
+
+impl Cache {
+    fn get(&mut self, k: &Cid) -> Option<Vec<u8>> {
+        let (data, last_used) = self.blocks.get_mut(k)?;
+        self.lru.remove(last_used);
+        self.tick += 1;
+        *last_used = self.tick;
+        self.lru.insert(self.tick, *k);
+        Some(data.clone())
+    }
+
+    fn insert(&mut self, k: Cid, data: Vec<u8>) {
+        if data.len() > self.max_bytes || self.blocks.contains_key(&k) {
+            return;
+        }
+        self.bytes += data.len();
+        self.tick += 1;
+        self.lru.insert(self.tick, k);
+        self.blocks.insert(k, (data, self.tick));
+
+        while self.bytes > self.max_bytes {
+            let (&last_used, &oldest) = self.lru.iter().next().expect("cache is over its limit");
+            self.lru.remove(&last_used);
+            let (data, _) = self
+                .blocks
+                .remove(&oldest)
+                .expect("evicted block is cached");
+            self.bytes -= data.len();
+        }
+    }
+}
+
+impl<BS> Blockstore for CachingBlockstore<BS>
+where
+    BS: Blockstore,
+{
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        if k.hash().code() != IDENTITY {
+            if let Some(data) = self.cache.borrow_mut().get(k) {
+                self.stats.borrow_mut().hits += 1;
+                return Ok(Some(data));
+            }
+            self.stats.borrow_mut().misses += 1;
+        }
+
+        let data = self.base.get(k)?;
+        let mut stats = self.stats.borrow_mut();
+        stats.r += 1;
+        if let Some(data) = &data {
+            stats.br += data.len();
+            if k.hash().code() != IDENTITY {
+                self.cache.borrow_mut().insert(*k, data.clone());
+            }
+        }
+        Ok(data)
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        if self.cache.borrow().blocks.contains_key(k) {
+            self.stats.borrow_mut().hits += 1;
+            return Ok(true);
+        }
+        let mut stats = self.stats.borrow_mut();
+        if k.hash().code() != IDENTITY {
+            stats.misses += 1;
+        }
+        stats.r += 1;
+        self.base.has(k)
+    }
+
+    fn put<D>(&self, code: Code, block: &Block<D>) -> Result<Cid>
+    where
+        D: AsRef<[u8]>,
+    {
+        let mut stats = self.stats.borrow_mut();
+        stats.w += 1;
+        stats.bw += block.as_ref().len();
+        self.base.put(code, block)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        let mut stats = self.stats.borrow_mut();
+        stats.w += 1;
+        stats.bw += block.len();
+        self.base.put_keyed(k, block)
+    }
+
+    fn put_many<D, I>(&self, blocks: I) -> Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (multihash::Code, Block<D>)>,
+    {
+        let mut stats = self.stats.borrow_mut();
+        self.base.put_many(blocks.into_iter().inspect(|(_, b)| {
+            stats.w += 1;
+            stats.bw += b.as_ref().len();
+        }))?;
+        Ok(())
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let mut stats = self.stats.borrow_mut();
+        self.base
+            .put_many_keyed(blocks.into_iter().inspect(|(_, b)| {
+                stats.w += 1;
+                stats.bw += b.as_ref().len();
+            }))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBlockstore;
+
+    #[test]
+    fn basic_caching_store() {
+        let mem = MemoryBlockstore::default();
+        let store = CachingBlockstore::new(&mem, 10);
+
+        let a = mem
+            .put(Code::Blake2b256, &Block::new(0x55, b"aaaa"))
+            .unwrap();
+        let b = mem
+            .put(Code::Blake2b256, &Block::new(0x55, b"bbbb"))
+            .unwrap();
+        let c = mem
+            .put(Code::Blake2b256, &Block::new(0x55, b"cccc"))
+            .unwrap();
+
+        // The first read misses, the second hits.
+        assert_eq!(store.get(&a).unwrap(), Some(b"aaaa".to_vec()));
+        assert_eq!(store.get(&a).unwrap(), Some(b"aaaa".to_vec()));
+        assert!(store.has(&a).unwrap());
+        assert_eq!(
+            *store.stats.borrow(),
+            BSStats {
+                r: 1,
+                br: 4,
+                hits: 2,
+                misses: 1,
+                ..Default::default()
+            }
+        );
+
+        // Reading `c` evicts `b`, the least recently used block.
+        store.get(&b).unwrap();
+        store.get(&a).unwrap();
+        store.get(&c).unwrap();
+        assert_eq!(store.cached_bytes(), 8);
+        *store.stats.borrow_mut() = BSStats::default();
+        store.get(&a).unwrap();
+        store.get(&c).unwrap();
+        store.get(&b).unwrap();
+        assert_eq!(
+            *store.stats.borrow(),
+            BSStats {
+                r: 1,
+                br: 4,
+                hits: 2,
+                misses: 1,
+                ..Default::default()
+            }
+        );
+
+        // Missing blocks aren't cached.
+        let missing = Block::new(0x55, b"dddd").cid(Code::Blake2b256);
+        assert_eq!(store.get(&missing).unwrap(), None);
+        assert_eq!(store.get(&missing).unwrap(), None);
+        assert_eq!(store.stats.borrow().misses, 3);
+
+        // Nor are blocks larger than the cache.
+        let large = mem
+            .put(Code::Blake2b256, &Block::new(0x55, [0u8; 11]))
+            .unwrap();
+        store.get(&large).unwrap();
+        assert_eq!(store.cached_bytes(), 8);
+    }
+
+    #[test]
+    fn identity_not_cached() {
+        let mem = MemoryBlockstore::default();
+        let store = CachingBlockstore::new(&mem, 1 << 20);
+
+        let identity = Cid::new_v1(0x55, multihash::Multihash::wrap(IDENTITY, b"data").unwrap());
+        store.put_keyed(&identity, b"data").unwrap();
+        store.get(&identity).unwrap();
+        store.get(&identity).unwrap();
+        assert_eq!(store.cached_bytes(), 0);
+        assert_eq!(
+            *store.stats.borrow(),
+            BSStats {
+                r: 2,
+                br: 8,
+                w: 1,
+                bw: 4,
+                ..Default::default()
+            }
+        );
+    }
+}
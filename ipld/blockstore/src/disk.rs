@@ -0,0 +1,462 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{anyhow, bail, Context, Result};
+use cid::Cid;
+
+use super::Blockstore;
+
+/// Identifies the log format.
+const MAGIC: &[u8; 8] = b"fvmbs\x00\x00\x01";
+
+/// Tags a block record: `BLOCK || cid length (u32) || data length (u32) || cid || data`.
+const BLOCK: u8 = 1;
+const BLOCK_HEADER_LEN: u64 = 9;
+
+/// Tags a commit record, which ends a batch: `COMMIT || batch offset (u64) || COMMIT_MAGIC`.
+const COMMIT: u8 = 2;
+const COMMIT_MAGIC: &[u8; 8] = b"fvmbsend";
+const COMMIT_LEN: u64 = 17;
+
+/// The location of a block's data in the file.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    offset: u64,
+    len: u64,
+}
+
+/// A persistent blockstore backed by a single file.
+///
+/// Blocks are appended to a log in batches, each ending with a commit record, and the log is
+/// indexed in memory when it's opened. Writes are durable once they return: the blocks of a batch
+/// are synced to disk before its commit record is written (and synced), so a partially written
+/// batch at the end of the log (e.g., after a crash) is detected and discarded when the log is
+/// next opened.
+///
+/// A blockstore can also be opened in place over a CAR file, read-only, with
+/// [`DiskBlockstore::open_car`].
+#[derive(Debug)]
+pub struct DiskBlockstore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    file: File,
+    index: HashMap<Cid, Location>,
+    /// The end of the committed log, where the next batch will be written.
+    end: u64,
+    read_only: bool,
+}
+
+impl DiskBlockstore {
+    /// Opens the blockstore log at `path`, creating it if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("failed to open blockstore {}", path.display()))?;
+
+        let len = file.metadata()?.len();
+        let (index, end) = if len < MAGIC.len() as u64 {
+            // A new log (or one we crashed while creating).
+            let mut prefix = Vec::new();
+            file.read_to_end(&mut prefix)?;
+            if !MAGIC.starts_with(&prefix) {
+                bail!("{} is not a blockstore", path.display());
+            }
+            file.set_len(0)?;
+            file.rewind()?;
+            file.write_all(MAGIC)?;
+            file.sync_all()?;
+            (HashMap::new(), MAGIC.len() as u64)
+        } else {
+            let (index, end) = scan_log(&mut file, len)
+                .with_context(|| format!("failed to read blockstore {}", path.display()))?;
+            if end < len {
+                // Discard the partially written batch.
+                file.set_len(end)?;
+                file.sync_all()?;
+            }
+            (index, end)
+        };
+
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                file,
+                index,
+                end,
+                read_only: false,
+            }),
+        })
+    }
+
+    /// Opens a CAR file as a read-only blockstore, indexing the blocks in place.
+    pub fn open_car(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path)
+            .with_context(|| format!("failed to open CAR file {}", path.display()))?;
+        let len = file.metadata()?.len();
+        let index = scan_car(&mut file, len)
+            .with_context(|| format!("failed to index CAR file {}", path.display()))?;
+
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                file,
+                index,
+                end: len,
+                read_only: true,
+            }),
+        })
+    }
+
+    fn inner(&self) -> Result<MutexGuard<'_, Inner>> {
+        self.inner
+            .lock()
+            .map_err(|_| anyhow!("blockstore lock poisoned"))
+    }
+}
+
+impl Blockstore for DiskBlockstore {
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        let mut inner = self.inner()?;
+        let loc = match inner.index.get(k) {
+            Some(loc) => *loc,
+            None => return Ok(None),
+        };
+        let mut data = vec![0; loc.len.try_into()?];
+        inner.file.seek(SeekFrom::Start(loc.offset))?;
+        inner.file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        Ok(self.inner()?.index.contains_key(k))
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.put_many_keyed([(*k, block)])
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let mut inner = self.inner()?;
+        if inner.read_only {
+            bail!("cannot write to a read-only blockstore");
+        }
+        let res = inner.write_batch(blocks);
+        if res.is_err() {
+            // Best effort: drop whatever we managed to write. The batch is ignored either way, as
+            // it wasn't committed.
+            let _ = inner.file.set_len(inner.end);
+        }
+        res
+    }
+}
+
+impl Inner {
+    fn write_batch<D, I>(&mut self, blocks: I) -> Result<()>
+    where
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let start = self.end;
+        let mut pos = start;
+        let mut added = HashMap::new();
+
+        self.file.seek(SeekFrom::Start(start))?;
+        let mut writer = BufWriter::new(&self.file);
+        for (cid, data) in blocks {
+            if self.index.contains_key(&cid) || added.contains_key(&cid) {
+                continue;
+            }
+            let data = data.as_ref();
+            let cid_bytes = cid.to_bytes();
+            writer.write_all(&[BLOCK])?;
+            writer.write_all(&u32::try_from(cid_bytes.len())?.to_le_bytes())?;
+            writer.write_all(&u32::try_from(data.len())?.to_le_bytes())?;
+            writer.write_all(&cid_bytes)?;
+            writer.write_all(data)?;
+
+            let offset = pos + BLOCK_HEADER_LEN + cid_bytes.len() as u64;
+            let len = data.len() as u64;
+            added.insert(cid, Location { offset, len });
+            pos = offset + len;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        if added.is_empty() {
+            return Ok(());
+        }
+
+        // Only commit once the blocks are on disk, so a commit never refers to a torn batch.
+        self.file.sync_data()?;
+        let mut commit = [0u8; COMMIT_LEN as usize];
+        commit[0] = COMMIT;
+        commit[1..9].copy_from_slice(&start.to_le_bytes());
+        commit[9..].copy_from_slice(COMMIT_MAGIC);
+        self.file.write_all(&commit)?;
+        self.file.sync_data()?;
+
+        self.end = pos + COMMIT_LEN;
+        self.index.extend(added);
+        Ok(())
+    }
+}
+
+/// Indexes a blockstore log, returning the index and the end of the last committed batch.
+fn scan_log(file: &mut File, len: u64) -> Result<(HashMap<Cid, Location>, u64)> {
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("not a blockstore");
+    }
+
+    let mut index = HashMap::new();
+    let mut pending = Vec::new();
+    let mut pos = MAGIC.len() as u64;
+    let mut end = pos;
+    // Anything that doesn't parse is the remainder of a batch we crashed while writing.
+    while pos < len {
+        let mut tag = [0u8];
+        reader.read_exact(&mut tag)?;
+        match tag[0] {
+            BLOCK if len - pos >= BLOCK_HEADER_LEN => {
+                let mut lens = [0u8; 8];
+                reader.read_exact(&mut lens)?;
+                let cid_len = u32::from_le_bytes(lens[..4].try_into().unwrap()) as u64;
+                let data_len = u32::from_le_bytes(lens[4..].try_into().unwrap()) as u64;
+                let offset = pos + BLOCK_HEADER_LEN + cid_len;
+                if offset + data_len > len {
+                    break;
+                }
+                let mut cid = vec![0; cid_len as usize];
+                reader.read_exact(&mut cid)?;
+                let cid = match Cid::try_from(cid) {
+                    Ok(cid) => cid,
+                    Err(_) => break,
+                };
+                reader.seek_relative(data_len as i64)?;
+                pending.push((
+                    cid,
+                    Location {
+                        offset,
+                        len: data_len,
+                    },
+                ));
+                pos = offset + data_len;
+            }
+            COMMIT if len - pos >= COMMIT_LEN => {
+                let mut commit = [0u8; COMMIT_LEN as usize - 1];
+                reader.read_exact(&mut commit)?;
+                let start = u64::from_le_bytes(commit[..8].try_into().unwrap());
+                if start != end || &commit[8..] != COMMIT_MAGIC {
+                    break;
+                }
+                index.extend(pending.drain(..));
+                pos += COMMIT_LEN;
+                end = pos;
+            }
+            _ => break,
+        }
+    }
+    Ok((index, end))
+}
+
+/// Indexes the blocks of a CAR (v1) file.
+fn scan_car(file: &mut File, len: u64) -> Result<HashMap<Cid, Location>> {
+    let mut reader = BufReader::new(file);
+    let header_len = read_uvarint(&mut reader)?.ok_or_else(|| anyhow!("empty CAR file"))?;
+    reader.seek_relative(header_len.try_into()?)?;
+    let mut pos = reader.stream_position()?;
+    if pos > len {
+        bail!("truncated CAR header");
+    }
+
+    let mut index = HashMap::new();
+    while let Some(section_len) = read_uvarint(&mut reader)? {
+        let start = reader.stream_position()?;
+        let end = start
+            .checked_add(section_len)
+            .filter(|end| *end <= len)
+            .ok_or_else(|| anyhow!("truncated CAR section at offset {}", pos))?;
+        let cid = Cid::read_bytes((&mut reader).take(section_len))
+            .with_context(|| format!("invalid CID in CAR section at offset {}", pos))?;
+        let offset = reader.stream_position()?;
+        index.insert(
+            cid,
+            Location {
+                offset,
+                len: end - offset,
+            },
+        );
+        reader.seek_relative((end - offset) as i64)?;
+        pos = end;
+    }
+    Ok(index)
+}
+
+/// Reads an unsigned LEB128 varint, returning `None` at the end of the input.
+fn read_uvarint(reader: &mut impl Read) -> io::Result<Option<u64>> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err(io::Error::new(ErrorKind::UnexpectedEof, "truncated varint"))
+            };
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(io::Error::new(ErrorKind::InvalidData, "varint overflow"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use multihash::Code::Blake2b256;
+
+    use super::*;
+    use crate::Block;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "fvm-disk-blockstore-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn block(data: &[u8]) -> (Cid, Vec<u8>) {
+        (Block::new(0x55, data).cid(Blake2b256), data.to_vec())
+    }
+
+    #[test]
+    fn persistence() {
+        let path = temp_path("persistence");
+        let (a, b, c) = (block(b"a"), block(b"b"), block(b"c"));
+
+        let bs = DiskBlockstore::open(&path).unwrap();
+        assert_eq!(bs.get(&a.0).unwrap(), None);
+        bs.put_keyed(&a.0, &a.1).unwrap();
+        bs.put_many_keyed([b.clone(), c.clone(), a.clone(), b.clone()])
+            .unwrap();
+        assert_eq!(bs.get(&a.0).unwrap(), Some(a.1.clone()));
+        drop(bs);
+
+        let len = fs::metadata(&path).unwrap().len();
+        let bs = DiskBlockstore::open(&path).unwrap();
+        for (cid, data) in [&a, &b, &c] {
+            assert!(bs.has(cid).unwrap());
+            assert_eq!(bs.get(cid).unwrap().as_ref(), Some(data));
+        }
+        // Blocks that are already present aren't written again.
+        bs.put_many_keyed([a, b, c]).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn crash_recovery() {
+        let path = temp_path("crash-recovery");
+        let (a, b, c) = (block(b"a"), block(b"b"), block(b"c"));
+
+        let bs = DiskBlockstore::open(&path).unwrap();
+        bs.put_keyed(&a.0, &a.1).unwrap();
+        drop(bs);
+        let committed = fs::read(&path).unwrap();
+
+        // A batch with a complete block record, but no commit.
+        let bs = DiskBlockstore::open(&path).unwrap();
+        bs.put_keyed(&b.0, &b.1).unwrap();
+        drop(bs);
+        let mut torn = fs::read(&path).unwrap();
+        torn.truncate(torn.len() - COMMIT_LEN as usize);
+        fs::write(&path, &torn).unwrap();
+
+        let bs = DiskBlockstore::open(&path).unwrap();
+        assert_eq!(bs.get(&a.0).unwrap(), Some(a.1.clone()));
+        assert_eq!(bs.get(&b.0).unwrap(), None);
+        assert_eq!(fs::read(&path).unwrap(), committed);
+        drop(bs);
+
+        // Every truncation of a batch is discarded.
+        for len in committed.len()..torn.len() {
+            fs::write(&path, &torn[..len]).unwrap();
+            let bs = DiskBlockstore::open(&path).unwrap();
+            assert_eq!(bs.get(&b.0).unwrap(), None);
+            bs.put_keyed(&c.0, &c.1).unwrap();
+            drop(bs);
+
+            let bs = DiskBlockstore::open(&path).unwrap();
+            assert_eq!(bs.get(&a.0).unwrap(), Some(a.1.clone()));
+            assert_eq!(bs.get(&c.0).unwrap(), Some(c.1.clone()));
+        }
+
+        // As is a log we crashed while creating.
+        fs::write(&path, &MAGIC[..3]).unwrap();
+        DiskBlockstore::open(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), MAGIC);
+
+        // But other files are rejected.
+        fs::write(&path, b"foo").unwrap();
+        assert!(DiskBlockstore::open(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn car() {
+        let path = temp_path("car");
+        let blocks = [block(b"a"), block(b"b"), block(b"c")];
+
+        // The header isn't interpreted.
+        let mut car = vec![6];
+        car.extend_from_slice(b"header");
+        for (cid, data) in &blocks {
+            let cid = cid.to_bytes();
+            car.push((cid.len() + data.len()) as u8);
+            car.extend_from_slice(&cid);
+            car.extend_from_slice(data);
+        }
+        fs::write(&path, &car).unwrap();
+
+        let bs = DiskBlockstore::open_car(&path).unwrap();
+        for (cid, data) in &blocks {
+            assert_eq!(bs.get(cid).unwrap().as_ref(), Some(data));
+        }
+        assert_eq!(bs.get(&block(b"d").0).unwrap(), None);
+        assert!(bs.put_keyed(&blocks[0].0, &blocks[0].1).is_err());
+
+        // Truncated files are rejected.
+        fs::write(&path, &car[..car.len() - 1]).unwrap();
+        assert!(DiskBlockstore::open_car(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -5,14 +5,23 @@ use std::rc::Rc;
 use anyhow::Result;
 use cid::{multihash, Cid};
 
+pub mod caching;
 pub mod tracking;
 
 mod memory;
 pub use memory::MemoryBlockstore;
 
+#[cfg(feature = "disk")]
+mod disk;
+#[cfg(feature = "disk")]
+pub use disk::DiskBlockstore;
+
 mod block;
 pub use block::*;
 
+mod proof;
+pub use proof::{Proof, ProofRecorder};
+
 /// An IPLD blockstore suitable for injection into the FVM.
 ///
 /// The cgo blockstore adapter implements this trait.
@@ -17,6 +17,16 @@ impl MemoryBlockstore {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns the CIDs of all blocks in the store, in no particular order.
+    pub fn cids(&self) -> Vec<Cid> {
+        self.blocks.borrow().keys().copied().collect()
+    }
+
+    /// Removes a block from the store, returning it if it was present.
+    pub fn remove(&self, k: &Cid) -> Option<Vec<u8>> {
+        self.blocks.borrow_mut().remove(k)
+    }
 }
 
 impl Blockstore for MemoryBlockstore {
@@ -0,0 +1,152 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::cell::RefCell;
+
+use anyhow::{anyhow, bail, Result};
+use cid::multihash::{self, MultihashDigest};
+use cid::Cid;
+
+use super::{Blockstore, MemoryBlockstore};
+
+/// The identity multihash code.
+const IDENTITY: u64 = 0x0;
+
+/// A Merkle proof: the blocks on the path from a root to some value (or to where the value would
+/// be, if it's absent).
+///
+/// A proof is checked by [verifying](Proof::verify) its blocks against the trusted root and then
+/// reading the value from the resulting blockstore, which fails if the proof doesn't cover it.
+/// Proofs are usually generated by reading the value through a [`ProofRecorder`].
+///
+/// The blocks are in the order they were read, root first, so a proof maps directly to a CAR file
+/// with a single root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    root: Cid,
+    blocks: Vec<(Cid, Vec<u8>)>,
+}
+
+impl Proof {
+    pub fn new(root: Cid, blocks: Vec<(Cid, Vec<u8>)>) -> Self {
+        Self { root, blocks }
+    }
+
+    /// Returns the root the proof was generated against.
+    pub fn root(&self) -> &Cid {
+        &self.root
+    }
+
+    /// Returns the blocks making up the proof.
+    pub fn blocks(&self) -> &[(Cid, Vec<u8>)] {
+        &self.blocks
+    }
+
+    pub fn into_blocks(self) -> Vec<(Cid, Vec<u8>)> {
+        self.blocks
+    }
+
+    /// Checks that the proof was generated against `root` and that every block matches its CID,
+    /// returning a blockstore containing only the blocks of the proof.
+    pub fn verify(&self, root: &Cid) -> Result<MemoryBlockstore> {
+        if self.root != *root {
+            bail!("proof is for root {}, expected {}", self.root, root);
+        }
+        let store = MemoryBlockstore::new();
+        for (cid, data) in &self.blocks {
+            let valid = match cid.hash().code() {
+                IDENTITY => cid.hash().digest() == data.as_slice(),
+                code => multihash::Code::try_from(code)?.digest(data) == *cid.hash(),
+            };
+            if !valid {
+                bail!("proof block doesn't match its CID {}", cid);
+            }
+            store.put_keyed(cid, data)?;
+        }
+        Ok(store)
+    }
+}
+
+/// Wrapper around a `Blockstore` that records the blocks read through it, to generate a [`Proof`].
+///
+/// Writes are rejected, as proofs must be generated from flushed state.
+#[derive(Debug)]
+pub struct ProofRecorder<BS> {
+    base: BS,
+    blocks: RefCell<Vec<(Cid, Vec<u8>)>>,
+}
+
+impl<BS> ProofRecorder<BS>
+where
+    BS: Blockstore,
+{
+    pub fn new(base: BS) -> Self {
+        Self {
+            base,
+            blocks: Default::default(),
+        }
+    }
+
+    /// Returns a proof made up of all the blocks read so far.
+    pub fn into_proof(self, root: Cid) -> Proof {
+        Proof::new(root, self.blocks.into_inner())
+    }
+}
+
+impl<BS> Blockstore for ProofRecorder<BS>
+where
+    BS: Blockstore,
+{
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        let block = self.base.get(k)?;
+        if let Some(block) = &block {
+            let mut blocks = self.blocks.borrow_mut();
+            if !blocks.iter().any(|(c, _)| c == k) {
+                blocks.push((*k, block.clone()));
+            }
+        }
+        Ok(block)
+    }
+
+    fn put_keyed(&self, k: &Cid, _: &[u8]) -> Result<()> {
+        Err(anyhow!("cannot write block {} while recording a proof", k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use multihash::Code::Blake2b256;
+
+    use super::*;
+    use crate::Block;
+
+    #[test]
+    fn record_and_verify() {
+        let mem = MemoryBlockstore::default();
+        let root = mem.put(Blake2b256, &Block::new(0x55, b"root")).unwrap();
+        let leaf = mem.put(Blake2b256, &Block::new(0x55, b"leaf")).unwrap();
+        let other = mem.put(Blake2b256, &Block::new(0x55, b"other")).unwrap();
+
+        let recorder = ProofRecorder::new(&mem);
+        recorder.get(&root).unwrap();
+        recorder.get(&leaf).unwrap();
+        recorder.get(&root).unwrap();
+        assert!(recorder.put_keyed(&other, b"other").is_err());
+        let proof = recorder.into_proof(root);
+        assert_eq!(
+            proof.blocks(),
+            &[(root, b"root".to_vec()), (leaf, b"leaf".to_vec())]
+        );
+
+        let store = proof.verify(&root).unwrap();
+        assert_eq!(store.get(&leaf).unwrap(), Some(b"leaf".to_vec()));
+        assert_eq!(store.get(&other).unwrap(), None);
+
+        // The proof must be for the expected root.
+        assert!(proof.verify(&leaf).is_err());
+
+        // And all blocks must match their CIDs.
+        let mut blocks = proof.into_blocks();
+        blocks[1].1 = b"fake".to_vec();
+        assert!(Proof::new(root, blocks).verify(&root).is_err());
+    }
+}
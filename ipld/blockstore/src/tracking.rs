@@ -10,8 +10,10 @@ use cid::Cid;
 
 use super::{Block, Blockstore};
 
-/// Stats for a [TrackingBlockstore] this indicates the amount of read and written data
-/// to the wrapped store.
+/// Stats for a [TrackingBlockstore] (or a [CachingBlockstore]) this indicates the amount of read
+/// and written data to the wrapped store.
+///
+/// [CachingBlockstore]: crate::caching::CachingBlockstore
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct BSStats {
     /// Number of reads
@@ -22,6 +24,10 @@ pub struct BSStats {
     pub br: usize,
     /// Bytes Written
     pub bw: usize,
+    /// Number of reads served from the cache
+    pub hits: usize,
+    /// Number of reads that missed the cache
+    pub misses: usize,
 }
 
 /// Wrapper around `Blockstore` to tracking reads and writes for verification.
@@ -139,6 +145,7 @@ mod tests {
                 br: block.len(),
                 w: 1,
                 bw: block.len(),
+                ..Default::default()
             }
         );
     }
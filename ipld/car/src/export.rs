@@ -0,0 +1,317 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+
+use cid::Cid;
+use futures::{AsyncWrite, AsyncWriteExt};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{dag_cbor_links, to_vec, DAG_CBOR, IPLD_RAW};
+
+use crate::util::{ld_write, ld_write_sync};
+use crate::{CarHeader, Error};
+
+/// The identity multihash code.
+const IDENTITY: u64 = 0x0;
+
+/// Options for [`export_car`] and [`export_car_async`].
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    /// The maximum depth to export, counting the roots as depth 0. Links in blocks at this depth
+    /// aren't followed.
+    ///
+    /// DEFAULT: `None` (unlimited)
+    pub max_depth: Option<u64>,
+
+    /// Skip raw blocks. This skips actor Wasm code, but only because it's stored as raw blocks,
+    /// along with any other raw blocks. Skipped blocks aren't read from the blockstore.
+    ///
+    /// DEFAULT: `false`
+    pub skip_raw: bool,
+
+    /// Skip these blocks (e.g., the code CIDs in the builtin actors manifest), whatever their
+    /// codec. Skipped blocks aren't read from the blockstore, so their links aren't followed.
+    ///
+    /// DEFAULT: empty
+    pub skip: HashSet<Cid>,
+}
+
+/// Writes every block reachable from the `roots` as a CAR file, with the `roots` in the header.
+///
+/// Only DAG-CBOR blocks are scanned for links. Each block is written once, in breadth-first order.
+/// Blocks with identity CIDs are followed but not written, as their data is inlined in the CID.
+/// Fails if a reachable block is missing from the store.
+pub fn export_car<BS, W>(
+    store: &BS,
+    roots: Vec<Cid>,
+    options: &ExportOptions,
+    writer: &mut W,
+) -> Result<(), Error>
+where
+    BS: Blockstore,
+    W: Write,
+{
+    let walker = DagWalker::new(store, &roots, options);
+    ld_write_sync(writer, &to_vec(&CarHeader::from(roots))?)?;
+    for block in walker {
+        let (cid, data) = block?;
+        ld_write_sync(writer, &[cid.to_bytes(), data].concat())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Like [`export_car`], but writes to an async writer.
+pub async fn export_car_async<BS, W>(
+    store: &BS,
+    roots: Vec<Cid>,
+    options: &ExportOptions,
+    writer: &mut W,
+) -> Result<(), Error>
+where
+    BS: Blockstore,
+    W: AsyncWrite + Send + Unpin,
+{
+    let walker = DagWalker::new(store, &roots, options);
+    ld_write(writer, &to_vec(&CarHeader::from(roots))?).await?;
+    for block in walker {
+        let (cid, data) = block?;
+        ld_write(writer, &[cid.to_bytes(), data].concat()).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Walks a DAG breadth-first, yielding each block to be exported. Walking breadth-first means
+/// blocks are always first reached at their minimum depth, so deduplicating them doesn't interact
+/// with the depth limit.
+struct DagWalker<'a, BS> {
+    store: &'a BS,
+    options: &'a ExportOptions,
+    queue: VecDeque<(Cid, u64)>,
+    seen: HashSet<Cid>,
+}
+
+impl<'a, BS> DagWalker<'a, BS>
+where
+    BS: Blockstore,
+{
+    fn new(store: &'a BS, roots: &[Cid], options: &'a ExportOptions) -> Self {
+        let mut walker = DagWalker {
+            store,
+            options,
+            queue: VecDeque::new(),
+            seen: HashSet::new(),
+        };
+        walker.enqueue(roots.iter().copied(), 0);
+        walker
+    }
+
+    fn enqueue(&mut self, cids: impl IntoIterator<Item = Cid>, depth: u64) {
+        for cid in cids {
+            let skip = (self.options.skip_raw && cid.codec() == IPLD_RAW)
+                || self.options.skip.contains(&cid);
+            if skip {
+                continue;
+            }
+            if self.seen.insert(cid) {
+                self.queue.push_back((cid, depth));
+            }
+        }
+    }
+
+    /// Visits a block, queuing its links. Returns the block if it should be written.
+    fn visit(&mut self, cid: Cid, depth: u64) -> Result<Option<(Cid, Vec<u8>)>, Error> {
+        let inline = cid.hash().code() == IDENTITY;
+        let data = if inline {
+            cid.hash().digest().to_vec()
+        } else {
+            self.store
+                .get(&cid)
+                .map_err(|e| Error::Other(e.to_string()))?
+                .ok_or_else(|| Error::Other(format!("block {} not found", cid)))?
+        };
+
+        let within_depth = match self.options.max_depth {
+            Some(max) => depth < max,
+            None => true,
+        };
+        if within_depth && cid.codec() == DAG_CBOR {
+            self.enqueue(dag_cbor_links(&data)?, depth + 1);
+        }
+
+        Ok((!inline).then_some((cid, data)))
+    }
+}
+
+impl<'a, BS> Iterator for DagWalker<'a, BS>
+where
+    BS: Blockstore,
+{
+    type Item = Result<(Cid, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((cid, depth)) = self.queue.pop_front() {
+            match self.visit(cid, depth) {
+                Ok(Some(block)) => return Some(Ok(block)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.queue.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::io::Cursor;
+    use cid::multihash::{Code, Multihash, MultihashDigest};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::CborStore;
+
+    use super::*;
+    use crate::CarReader;
+
+    struct Dag {
+        store: MemoryBlockstore,
+        root: Cid,
+        children: [Cid; 3],
+        grandchild: Cid,
+    }
+
+    /// `root -> [left, right, code]`, where `left -> [grandchild]` and
+    /// `right -> [grandchild, inline -> [grandchild]]`.
+    fn dag() -> Dag {
+        let store = MemoryBlockstore::new();
+        let grandchild = store.put_cbor(&"grandchild", Code::Blake2b256).unwrap();
+        let inline = to_vec(&(grandchild,)).unwrap();
+        let inline = Cid::new_v1(DAG_CBOR, Multihash::wrap(IDENTITY, &inline).unwrap());
+        let left = store.put_cbor(&(grandchild,), Code::Blake2b256).unwrap();
+        let right = store
+            .put_cbor(&(grandchild, inline), Code::Blake2b256)
+            .unwrap();
+        let code = Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(b"code"));
+        store.put_keyed(&code, b"code").unwrap();
+        let root = store
+            .put_cbor(&(left, right, code), Code::Blake2b256)
+            .unwrap();
+        Dag {
+            store,
+            root,
+            children: [left, right, code],
+            grandchild,
+        }
+    }
+
+    async fn read_cids(car: &[u8]) -> (Vec<Cid>, Vec<Cid>) {
+        let mut reader = CarReader::new(Cursor::new(car)).await.unwrap();
+        let mut cids = Vec::new();
+        while let Some(block) = reader.next_block().await.unwrap() {
+            cids.push(block.cid);
+        }
+        (reader.header.roots, cids)
+    }
+
+    fn export(store: &MemoryBlockstore, roots: Vec<Cid>, options: &ExportOptions) -> Vec<u8> {
+        let mut car = Vec::new();
+        export_car(store, roots, options, &mut car).unwrap();
+        car
+    }
+
+    #[async_std::test]
+    async fn export_dag() {
+        let Dag {
+            store,
+            root,
+            children: [left, right, code],
+            grandchild,
+        } = dag();
+
+        let car = export(&store, vec![root], &ExportOptions::default());
+        let (roots, cids) = read_cids(&car).await;
+        assert_eq!(roots, vec![root]);
+        assert_eq!(cids, vec![root, left, right, code, grandchild]);
+
+        let mut async_car = Vec::new();
+        export_car_async(
+            &store,
+            vec![root],
+            &ExportOptions::default(),
+            &mut async_car,
+        )
+        .await
+        .unwrap();
+        assert_eq!(async_car, car);
+
+        // Subgraphs of the roots are only written once.
+        let (roots, cids) =
+            read_cids(&export(&store, vec![left, root], &ExportOptions::default())).await;
+        assert_eq!(roots, vec![left, root]);
+        assert_eq!(cids, vec![left, root, grandchild, right, code]);
+    }
+
+    #[async_std::test]
+    async fn export_options() {
+        let Dag {
+            store,
+            root,
+            children: [left, right, code],
+            grandchild,
+        } = dag();
+
+        let options = ExportOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let (_, cids) = read_cids(&export(&store, vec![root], &options)).await;
+        assert_eq!(cids, vec![root, left, right, code]);
+
+        let options = ExportOptions {
+            max_depth: Some(0),
+            skip_raw: true,
+            ..Default::default()
+        };
+        let (_, cids) = read_cids(&export(&store, vec![root], &options)).await;
+        assert_eq!(cids, vec![root]);
+
+        let options = ExportOptions {
+            skip_raw: true,
+            ..Default::default()
+        };
+        let (_, cids) = read_cids(&export(&store, vec![root], &options)).await;
+        assert!(!cids.contains(&code));
+        assert_eq!(cids.len(), 4);
+
+        let options = ExportOptions {
+            skip: HashSet::from([code, left]),
+            ..Default::default()
+        };
+        let (_, cids) = read_cids(&export(&store, vec![root], &options)).await;
+        assert_eq!(cids, vec![root, right, grandchild]);
+    }
+
+    #[test]
+    fn export_missing_block() {
+        let Dag {
+            store,
+            root,
+            grandchild,
+            ..
+        } = dag();
+        store.remove(&grandchild);
+
+        let mut car = Vec::new();
+        assert!(export_car(&store, vec![root], &ExportOptions::default(), &mut car).is_err());
+
+        // Blocks beyond the depth limit don't need to be present.
+        let options = ExportOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        export_car(&store, vec![root], &options, &mut car).unwrap();
+    }
+}
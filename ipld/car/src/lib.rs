@@ -3,14 +3,16 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 mod error;
+mod export;
 mod util;
 
 use std::convert::TryFrom;
 
 use cid::Cid;
 pub use error::*;
+pub use export::{export_car, export_car_async, ExportOptions};
 use futures::{AsyncRead, AsyncWrite, Stream, StreamExt};
-use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_blockstore::{Blockstore, Proof};
 use fvm_ipld_encoding::{from_slice, to_vec};
 use serde::{Deserialize, Serialize};
 use util::{ld_read, ld_write, read_node};
@@ -180,6 +182,38 @@ where
     Ok(car_reader.header.roots)
 }
 
+/// Writes a [`Proof`] as a CAR file, with the root of the proof as the only root.
+pub async fn write_proof<W>(writer: &mut W, proof: &Proof) -> Result<(), Error>
+where
+    W: AsyncWrite + Send + Unpin,
+{
+    let header = CarHeader::from(vec![*proof.root()]);
+    let mut blocks = futures::stream::iter(proof.blocks().iter().cloned());
+    header.write_stream_async(writer, &mut blocks).await
+}
+
+/// Reads a [`Proof`] from a CAR file with a single root, checking the CIDs of the blocks. The proof
+/// must still be verified against a trusted root.
+pub async fn read_proof<R>(reader: R) -> Result<Proof, Error>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let mut car_reader = CarReader::new(reader).await?;
+    let root = match car_reader.header.roots[..] {
+        [root] => root,
+        _ => {
+            return Err(Error::InvalidFile(
+                "a proof must have exactly one root".to_owned(),
+            ))
+        }
+    };
+    let mut blocks = Vec::new();
+    while let Some(Block { cid, data }) = car_reader.next_block().await? {
+        blocks.push((cid, data));
+    }
+    Ok(Proof::new(root, blocks))
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -239,4 +273,26 @@ mod tests {
 
         assert_eq!(bs.get(&cid).unwrap(), Some(b"test".to_vec()));
     }
+
+    #[async_std::test]
+    async fn proof_write_read() {
+        let root = Cid::new_v1(DAG_CBOR, Blake2b256.digest(b"root"));
+        let leaf = Cid::new_v1(DAG_CBOR, Blake2b256.digest(b"leaf"));
+        let proof = Proof::new(
+            root,
+            vec![(root, b"root".to_vec()), (leaf, b"leaf".to_vec())],
+        );
+
+        let mut buffer = Vec::new();
+        write_proof(&mut buffer, &proof).await.unwrap();
+        assert_eq!(read_proof(Cursor::new(&buffer)).await.unwrap(), proof);
+
+        // A proof must have a single root.
+        let mut buffer = Vec::new();
+        CarHeader::from(vec![root, leaf])
+            .write_stream_async(&mut buffer, &mut futures::stream::empty())
+            .await
+            .unwrap();
+        assert!(read_proof(Cursor::new(&buffer)).await.is_err());
+    }
 }
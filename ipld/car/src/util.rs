@@ -2,9 +2,11 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::io::Write;
+
 use cid::Cid;
 use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use integer_encoding::{VarIntAsyncReader, VarIntAsyncWriter};
+use integer_encoding::{VarIntAsyncReader, VarIntAsyncWriter, VarIntWriter};
 
 use super::error::Error;
 
@@ -50,6 +52,15 @@ where
     Ok(())
 }
 
+pub(crate) fn ld_write_sync<W>(writer: &mut W, bytes: &[u8]) -> Result<(), Error>
+where
+    W: Write,
+{
+    writer.write_varint(bytes.len())?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
 pub(crate) async fn read_node<R>(buf_reader: &mut R) -> Result<Option<(Cid, Vec<u8>)>, Error>
 where
     R: AsyncRead + Send + Unpin,
@@ -8,6 +8,7 @@ mod cbor_store;
 mod errors;
 pub mod ipld_block;
 mod raw;
+mod validate;
 mod vec;
 use std::io;
 
@@ -17,6 +18,7 @@ pub use self::bytes::*;
 pub use self::cbor::*;
 pub use self::cbor_store::CborStore;
 pub use self::errors::*;
+pub use self::validate::{dag_cbor_links, validate_dag_cbor};
 pub use self::vec::*;
 
 /// CBOR should be used to pass CBOR data when internal links don't need to be
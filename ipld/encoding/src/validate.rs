@@ -0,0 +1,203 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::fmt;
+
+use cid::Cid;
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::{from_slice, Error};
+
+/// Checks that `data` is a single, well-formed DAG-CBOR object, without decoding it into anything.
+///
+/// Unlike decoding into [`de::IgnoredAny`], this rejects tags other than CIDs (tag 42), invalid
+/// CIDs, and map keys that aren't strings.
+pub fn validate_dag_cbor(data: &[u8]) -> Result<(), Error> {
+    from_slice::<DagCbor<()>>(data).map(|_| ())
+}
+
+/// Returns the CIDs linked to by the DAG-CBOR object in `data`, in the order they appear. The data
+/// is validated exactly like [`validate_dag_cbor`].
+pub fn dag_cbor_links(data: &[u8]) -> Result<Vec<Cid>, Error> {
+    from_slice::<DagCbor<Vec<Cid>>>(data).map(|DagCbor(links)| links)
+}
+
+/// Where the links found while parsing a DAG-CBOR object go.
+trait Links: Default {
+    fn push(&mut self, link: Cid);
+}
+
+/// Discards the links, for validating without allocating.
+impl Links for () {
+    fn push(&mut self, _: Cid) {}
+}
+
+impl Links for Vec<Cid> {
+    fn push(&mut self, link: Cid) {
+        Vec::push(self, link)
+    }
+}
+
+/// Any valid DAG-CBOR object, discarded as it's parsed except for the links it contains.
+struct DagCbor<L>(L);
+
+impl<'de, L: Links> Deserialize<'de> for DagCbor<L> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut links = L::default();
+        deserializer.deserialize_any(DagCborVisitor(&mut links))?;
+        Ok(DagCbor(links))
+    }
+}
+
+/// Parses a DAG-CBOR object, adding the links it contains to `L`.
+struct DagCborVisitor<'a, L>(&'a mut L);
+
+impl<'de, 'a, L: Links> DeserializeSeed<'de> for DagCborVisitor<'a, L> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a, L: Links> Visitor<'de> for DagCborVisitor<'a, L> {
+    type Value = ();
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("any valid DAG-CBOR object")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i128<E>(self, _: i128) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_bytes<E>(self, _: &[u8]) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// CIDs are the only newtypes.
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_bytes(CidBytes(self.0))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let links = self.0;
+        while let Some(()) = seq.next_element_seed(DagCborVisitor(&mut *links))? {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let links = self.0;
+        while map.next_key::<String>()?.is_some() {
+            map.next_value_seed(DagCborVisitor(&mut *links))?;
+        }
+        Ok(())
+    }
+}
+
+/// The bytes of a CID, as passed to [`DagCborVisitor::visit_newtype_struct`].
+struct CidBytes<'a, L>(&'a mut L);
+
+impl<'de, 'a, L: Links> Visitor<'de> for CidBytes<'a, L> {
+    type Value = ();
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("a valid CID")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<(), E> {
+        let cid = Cid::try_from(bytes).map_err(E::custom)?;
+        self.0.push(cid);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+
+    use super::*;
+    use crate::{to_vec, BytesSer, DAG_CBOR};
+
+    #[test]
+    fn valid() {
+        let cid = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(b"foo"));
+        let value = (
+            "foo",
+            -1i64,
+            u64::MAX,
+            1.5f64,
+            BytesSer(b"bar"),
+            vec![Some(cid), None],
+            std::collections::BTreeMap::from([("a", true), ("b", false)]),
+        );
+        let data = to_vec(&value).unwrap();
+        validate_dag_cbor(&data).unwrap();
+        assert_eq!(dag_cbor_links(&data).unwrap(), vec![cid]);
+    }
+
+    #[test]
+    fn links() {
+        let a = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(b"a"));
+        let b = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(b"b"));
+        let value = (
+            a,
+            std::collections::BTreeMap::from([("x", vec![b]), ("y", vec![a, b])]),
+        );
+        assert_eq!(
+            dag_cbor_links(&to_vec(&value).unwrap()).unwrap(),
+            vec![a, b, a, b]
+        );
+        assert!(dag_cbor_links(&to_vec(&"no links").unwrap())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        for data in [
+            &[][..],
+            // Trailing data
+            &[0x01, 0x02],
+            // Truncated string
+            &[0x62, 0x61],
+            // A tag other than 42
+            &[0xc1, 0x01],
+            // Tag 42 around something other than a CID
+            &[0xd8, 0x2a, 0x41, 0x00],
+            // A map with an integer key
+            &[0xa1, 0x01, 0x02],
+        ] {
+            assert!(validate_dag_cbor(data).is_err(), "{:x?} is valid", data);
+            assert!(dag_cbor_links(data).is_err(), "{:x?} is valid", data);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use fvm_ipld_blockstore::Blockstore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::node::Node;
+use crate::pointer::Pointer;
+use crate::{Error, Hash, HashAlgorithm, KeyValuePair};
+
+/// A change to a single key between two versions of a map, see [`Hamt::diff`](crate::Hamt::diff).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<K, V> {
+    /// The key was added.
+    Added { key: K, value: V },
+    /// The key was removed.
+    Removed { key: K, value: V },
+    /// The key's value was modified.
+    Modified { key: K, before: V, after: V },
+}
+
+impl<K, V> Change<K, V> {
+    /// Returns the changed key.
+    pub fn key(&self) -> &K {
+        match self {
+            Change::Added { key, .. }
+            | Change::Removed { key, .. }
+            | Change::Modified { key, .. } => key,
+        }
+    }
+}
+
+/// Walks two nodes in parallel, skipping any child links with equal CIDs.
+pub(crate) struct Differ<'a, BS1, BS2> {
+    pub bit_width: u32,
+    pub before_store: &'a BS1,
+    pub after_store: &'a BS2,
+}
+
+impl<'a, BS1, BS2> Differ<'a, BS1, BS2>
+where
+    BS1: Blockstore,
+    BS2: Blockstore,
+{
+    pub fn diff_nodes<K, V, H>(
+        &self,
+        before: &'a Node<K, V, H>,
+        after: &'a Node<K, V, H>,
+        changes: &mut Vec<Change<K, V>>,
+    ) -> Result<(), Error>
+    where
+        K: Hash + Eq + PartialOrd + Serialize + DeserializeOwned + Clone,
+        V: Serialize + DeserializeOwned + PartialEq + Clone,
+        H: HashAlgorithm,
+    {
+        for idx in 0..(1 << self.bit_width) {
+            let before_ptr = before
+                .bitfield
+                .test_bit(idx)
+                .then(|| &before.pointers[before.index_for_bit_pos(idx)]);
+            let after_ptr = after
+                .bitfield
+                .test_bit(idx)
+                .then(|| &after.pointers[after.index_for_bit_pos(idx)]);
+
+            match (before_ptr, after_ptr) {
+                (None, None) => {}
+                (Some(Pointer::Link { cid: a, .. }), Some(Pointer::Link { cid: b, .. }))
+                    if a == b => {}
+                (Some(Pointer::Values(a)), Some(Pointer::Values(b))) => {
+                    diff_values(a.iter().collect(), b.iter().collect(), changes)
+                }
+                (Some(a), Some(b)) => {
+                    match (a.node(self.before_store)?, b.node(self.after_store)?) {
+                        (Some(a), Some(b)) => self.diff_nodes(a, b, changes)?,
+                        // A bucket was pushed down into a sub-node (or a sub-node was collapsed
+                        // into a bucket). One side is small, so just compare the entries.
+                        _ => {
+                            let mut a_values = Vec::new();
+                            collect_values(a, self.before_store, &mut a_values)?;
+                            let mut b_values = Vec::new();
+                            collect_values(b, self.after_store, &mut b_values)?;
+                            diff_values(a_values, b_values, changes);
+                        }
+                    }
+                }
+                (Some(a), None) => {
+                    let mut values = Vec::new();
+                    collect_values(a, self.before_store, &mut values)?;
+                    changes.extend(values.into_iter().map(|kv| Change::Removed {
+                        key: kv.0.clone(),
+                        value: kv.1.clone(),
+                    }));
+                }
+                (None, Some(b)) => {
+                    let mut values = Vec::new();
+                    collect_values(b, self.after_store, &mut values)?;
+                    changes.extend(values.into_iter().map(|kv| Change::Added {
+                        key: kv.0.clone(),
+                        value: kv.1.clone(),
+                    }));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Collects all key-value pairs under a pointer.
+fn collect_values<'a, K, V, H, BS>(
+    ptr: &'a Pointer<K, V, H>,
+    store: &BS,
+    values: &mut Vec<&'a KeyValuePair<K, V>>,
+) -> Result<(), Error>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    BS: Blockstore,
+{
+    match ptr.node(store)? {
+        Some(node) => {
+            for ptr in &node.pointers {
+                collect_values(ptr, store, values)?;
+            }
+        }
+        None => {
+            if let Pointer::Values(kvs) = ptr {
+                values.extend(kvs);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compares two sets of key-value pairs. At least one of the sets is expected to be small (a
+/// single bucket).
+fn diff_values<K, V>(
+    before: Vec<&KeyValuePair<K, V>>,
+    after: Vec<&KeyValuePair<K, V>>,
+    changes: &mut Vec<Change<K, V>>,
+) where
+    K: PartialEq + Clone,
+    V: PartialEq + Clone,
+{
+    for a in &before {
+        match after.iter().find(|b| a.key() == b.key()) {
+            Some(b) if a.value() != b.value() => changes.push(Change::Modified {
+                key: a.key().clone(),
+                before: a.value().clone(),
+                after: b.value().clone(),
+            }),
+            Some(_) => {}
+            None => changes.push(Change::Removed {
+                key: a.key().clone(),
+                value: a.value().clone(),
+            }),
+        }
+    }
+    for b in after {
+        if !before.iter().any(|a| a.key() == b.key()) {
+            changes.push(Change::Added {
+                key: b.key().clone(),
+                value: b.value().clone(),
+            });
+        }
+    }
+}
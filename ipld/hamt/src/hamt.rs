@@ -7,12 +7,14 @@ use std::marker::PhantomData;
 
 use cid::Cid;
 use forest_hash_utils::BytesKey;
-use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore, Proof, ProofRecorder};
 use fvm_ipld_encoding::CborStore;
 use multihash::Code;
 use serde::de::DeserializeOwned;
 use serde::{Serialize, Serializer};
 
+use crate::diff::{Change, Differ};
+use crate::iter::{Cursor, Iter};
 use crate::node::Node;
 use crate::{Config, Error, Hash, HashAlgorithm, Sha256};
 
@@ -362,8 +364,170 @@ where
         self.root.for_each(self.store.borrow(), &mut f)
     }
 
+    /// Returns an iterator over the entries of the HAMT, in the order of their hashed keys.
+    ///
+    /// Unlike [`Hamt::for_each`], iteration can be stopped at any point and later resumed from
+    /// the iterator's [`cursor`](Iter::cursor) with [`Hamt::iter_from`], even if the HAMT has been
+    /// modified (or reloaded) in the meantime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fvm_ipld_hamt::Hamt;
+    ///
+    /// let store = fvm_ipld_blockstore::MemoryBlockstore::default();
+    ///
+    /// let mut map: Hamt<_, _, usize> = Hamt::new(store);
+    /// for i in 0..10 {
+    ///     map.set(i, i * 2).unwrap();
+    /// }
+    ///
+    /// // Page through the map, 3 entries at a time.
+    /// let mut cursor = None;
+    /// let mut total = 0;
+    /// loop {
+    ///     let mut iter = match &cursor {
+    ///         Some(cursor) => map.iter_from(cursor).unwrap(),
+    ///         None => map.iter(),
+    ///     };
+    ///     let page = iter.by_ref().take(3).collect::<Result<Vec<_>, _>>().unwrap();
+    ///     total += page.iter().map(|(_, v)| **v).sum::<usize>();
+    ///     if page.len() < 3 {
+    ///         break;
+    ///     }
+    ///     cursor = iter.cursor();
+    /// }
+    /// assert_eq!(total, 90);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, BS, V, K, H> {
+        Iter::new(&self.store, &self.root)
+    }
+
+    /// Returns an iterator over the entries of the HAMT that come after the cursor, in the order of
+    /// their hashed keys. The iterator descends straight to the cursor, without visiting the
+    /// entries before it.
+    pub fn iter_from(&self, cursor: &Cursor) -> Result<Iter<'_, BS, V, K, H>, Error> {
+        Iter::new_from(&self.store, &self.conf, &self.root, cursor)
+    }
+
+    /// Computes the changes from this HAMT to `other`, which may live in a different store.
+    ///
+    /// Both HAMTs are walked in parallel and sub-trees with equal CIDs are skipped, so the cost
+    /// is proportional to the size of the change rather than the size of the maps. Both HAMTs must
+    /// use the same bit width.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fvm_ipld_hamt::{Change, Hamt};
+    ///
+    /// let store = fvm_ipld_blockstore::MemoryBlockstore::default();
+    ///
+    /// let mut before: Hamt<_, _, usize> = Hamt::new(&store);
+    /// before.set(1, "a".to_string()).unwrap();
+    /// before.set(2, "b".to_string()).unwrap();
+    ///
+    /// let mut after: Hamt<_, _, usize> = Hamt::new(&store);
+    /// after.set(2, "c".to_string()).unwrap();
+    ///
+    /// let changes = before.diff(&after).unwrap();
+    /// assert_eq!(changes.len(), 2);
+    /// assert!(changes.contains(&Change::Removed { key: 1, value: "a".to_string() }));
+    /// assert!(changes.contains(&Change::Modified {
+    ///     key: 2,
+    ///     before: "b".to_string(),
+    ///     after: "c".to_string(),
+    /// }));
+    /// ```
+    pub fn diff<BS2>(&self, other: &Hamt<BS2, V, K, H>) -> Result<Vec<Change<K, V>>, Error>
+    where
+        K: Clone,
+        V: PartialEq + Clone,
+        BS2: Blockstore,
+    {
+        if self.conf.bit_width != other.conf.bit_width {
+            return Err("cannot diff HAMTs with different bit widths".into());
+        }
+        let mut changes = Vec::new();
+        if self.flushed_cid.is_some() && self.flushed_cid == other.flushed_cid {
+            return Ok(changes);
+        }
+        let differ = Differ {
+            bit_width: self.conf.bit_width,
+            before_store: &self.store,
+            after_store: &other.store,
+        };
+        differ.diff_nodes(&self.root, &other.root, &mut changes)?;
+        Ok(changes)
+    }
+
+    /// Generates a proof of the value of `k` (or of its absence) in this HAMT, which must have been
+    /// flushed. The proof is made up of the blocks on the path to `k`, and can be checked with
+    /// [`Hamt::verify_proof`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fvm_ipld_hamt::{Config, Hamt};
+    ///
+    /// let store = fvm_ipld_blockstore::MemoryBlockstore::default();
+    ///
+    /// let mut map: Hamt<_, _, usize> = Hamt::new(&store);
+    /// for i in 0..100 {
+    ///     map.set(i, i.to_string()).unwrap();
+    /// }
+    /// let root = map.flush().unwrap();
+    ///
+    /// let proof = map.prove(&7).unwrap();
+    /// let value = Hamt::<_, String, usize>::verify_proof(&proof, &root, &7, Config::default());
+    /// assert_eq!(value.unwrap(), Some("7".to_string()));
+    ///
+    /// let proof = map.prove(&100).unwrap();
+    /// let value = Hamt::<_, String, usize>::verify_proof(&proof, &root, &100, Config::default());
+    /// assert_eq!(value.unwrap(), None);
+    /// ```
+    pub fn prove<Q: ?Sized>(&self, k: &Q) -> Result<Proof, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let root = self
+            .flushed_cid
+            .ok_or("HAMT must be flushed before generating a proof")?;
+        let recorder = ProofRecorder::new(&self.store);
+        Hamt::<_, V, K, H>::load_with_config(&root, &recorder, self.conf.clone())?.get(k)?;
+        Ok(recorder.into_proof(root))
+    }
+
     /// Consumes this HAMT and returns the Blockstore it owns.
     pub fn into_store(self) -> BS {
         self.store
     }
 }
+
+impl<V, K, H> Hamt<MemoryBlockstore, V, K, H>
+where
+    K: Hash + Eq + PartialOrd + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned + Clone,
+    H: HashAlgorithm,
+{
+    /// Verifies a proof generated by [`Hamt::prove`] against the trusted HAMT root `root`, and
+    /// returns the value of `k` (or `None` if it's absent). Fails if the proof is invalid or
+    /// doesn't cover `k`.
+    ///
+    /// Note: absence can't be proven with the `ignore-dead-links` feature enabled, as blocks
+    /// missing from the proof are then skipped.
+    pub fn verify_proof<Q: ?Sized>(
+        proof: &Proof,
+        root: &Cid,
+        k: &Q,
+        conf: Config,
+    ) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let store = proof.verify(root)?;
+        Ok(Self::load_with_config(root, store, conf)?.get(k)?.cloned())
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::cmp::Reverse;
+use std::marker::PhantomData;
+
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::strict_bytes;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::hash_bits::HashBits;
+use crate::node::Node;
+use crate::pointer::Pointer;
+use crate::{Config, Error, Hash, HashAlgorithm, HashedKey, KeyValuePair};
+
+/// A position in a HAMT, used to resume iteration (see [`Hamt::iter_from`](crate::Hamt::iter_from)).
+///
+/// HAMTs are iterated in the order of their hashed keys, so a cursor is simply the hash of the
+/// last key visited. Cursors remain valid when the HAMT is modified: iteration resumes with the
+/// first key (in hash order) after the cursor, whether or not the last key still exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cursor(#[serde(with = "strict_bytes")] HashedKey);
+
+impl Cursor {
+    /// Returns the hashed key this cursor points to.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// An iterator over the entries of a HAMT, in the order of their hashed keys. See
+/// [`Hamt::iter`](crate::Hamt::iter).
+pub struct Iter<'a, BS, V, K, H> {
+    store: &'a BS,
+    /// The pointers left to visit at each level of the traversal, deepest last.
+    stack: Vec<std::slice::Iter<'a, Pointer<K, V, H>>>,
+    /// The entries left to visit in the current bucket, in descending hash order.
+    bucket: Vec<(HashedKey, &'a KeyValuePair<K, V>)>,
+    cursor: Option<Cursor>,
+    hash: PhantomData<H>,
+}
+
+impl<'a, BS, V, K, H> Iter<'a, BS, V, K, H>
+where
+    K: Hash + Eq + PartialOrd + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+    H: HashAlgorithm,
+{
+    pub(crate) fn new(store: &'a BS, root: &'a Node<K, V, H>) -> Self {
+        Iter {
+            store,
+            stack: vec![root.pointers.iter()],
+            bucket: Vec::new(),
+            cursor: None,
+            hash: PhantomData,
+        }
+    }
+
+    /// Creates an iterator positioned right after the cursor, descending straight to it.
+    pub(crate) fn new_from(
+        store: &'a BS,
+        conf: &Config,
+        root: &'a Node<K, V, H>,
+        cursor: &Cursor,
+    ) -> Result<Self, Error> {
+        let mut iter = Iter {
+            store,
+            stack: Vec::new(),
+            bucket: Vec::new(),
+            cursor: Some(*cursor),
+            hash: PhantomData,
+        };
+
+        let mut hashed_key = HashBits::new(&cursor.0);
+        let mut node = root;
+        loop {
+            let idx = hashed_key.next(conf.bit_width)?;
+            let cindex = node.index_for_bit_pos(idx);
+            if !node.bitfield.test_bit(idx) {
+                // Nothing at the cursor, resume with the next pointer.
+                iter.stack.push(node.pointers[cindex..].iter());
+                break;
+            }
+
+            iter.stack.push(node.pointers[cindex + 1..].iter());
+            let ptr = &node.pointers[cindex];
+            match ptr.node(store)? {
+                Some(child) => node = child,
+                None => {
+                    iter.fill_bucket(ptr);
+                    iter.bucket.retain(|(hash, _)| *hash > cursor.0);
+                    break;
+                }
+            }
+        }
+        Ok(iter)
+    }
+
+    /// Returns a cursor pointing to the last entry returned by the iterator (or the cursor the
+    /// iterator was created from, if it hasn't returned any entries yet).
+    pub fn cursor(&self) -> Option<Cursor> {
+        self.cursor
+    }
+
+    fn fill_bucket(&mut self, ptr: &'a Pointer<K, V, H>) {
+        if let Pointer::Values(kvs) = ptr {
+            self.bucket = kvs.iter().map(|kv| (H::hash(kv.key()), kv)).collect();
+            // Sort in descending order so we can pop entries off the end.
+            self.bucket.sort_unstable_by_key(|&(hash, _)| Reverse(hash));
+        }
+    }
+}
+
+impl<'a, BS, V, K, H> Iterator for Iter<'a, BS, V, K, H>
+where
+    K: Hash + Eq + PartialOrd + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+    H: HashAlgorithm,
+{
+    type Item = Result<(&'a K, &'a V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((hash, kv)) = self.bucket.pop() {
+                self.cursor = Some(Cursor(hash));
+                return Some(Ok((kv.key(), kv.value())));
+            }
+
+            let ptr = match self.stack.last_mut()?.next() {
+                Some(ptr) => ptr,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            match ptr.node(self.store) {
+                Ok(Some(node)) => self.stack.push(node.pointers.iter()),
+                Ok(None) => self.fill_bucket(ptr),
+                Err(e) => {
+                    // Don't try to continue after an error.
+                    self.stack.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
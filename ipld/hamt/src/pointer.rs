@@ -6,6 +6,8 @@ use std::cmp::Ordering;
 use std::convert::{TryFrom, TryInto};
 
 use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
 use libipld_core::ipld::Ipld;
 use once_cell::unsync::OnceCell;
 use serde::de::{self, DeserializeOwned};
@@ -178,6 +180,29 @@ where
     }
 }
 
+impl<K, V, H> Pointer<K, V, H>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+{
+    /// Returns the node this pointer points to (loading and caching it, if necessary), or `None`
+    /// if the pointer holds values.
+    pub(crate) fn node<S: Blockstore>(&self, store: &S) -> Result<Option<&Node<K, V, H>>, Error> {
+        match self {
+            Pointer::Link { cid, cache } => {
+                let node = cache.get_or_try_init(|| {
+                    store
+                        .get_cbor(cid)?
+                        .ok_or_else(|| Error::CidNotFound(cid.to_string()))
+                })?;
+                Ok(Some(node))
+            }
+            Pointer::Dirty(node) => Ok(Some(node)),
+            Pointer::Values(_) => Ok(None),
+        }
+    }
+}
+
 fn from_ipld<T: DeserializeOwned>(ipld: Ipld) -> Result<T, String> {
     Deserialize::deserialize(ipld).map_err(|error| error.to_string())
 }
@@ -7,13 +7,13 @@ use std::fmt::Display;
 
 use cid::Cid;
 use fvm_ipld_blockstore::tracking::{BSStats, TrackingBlockstore};
-use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore, Proof};
 use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::strict_bytes::ByteBuf;
 use fvm_ipld_encoding::CborStore;
 #[cfg(feature = "identity")]
 use fvm_ipld_hamt::Identity;
-use fvm_ipld_hamt::{BytesKey, Config, Error, Hamt, Hash};
+use fvm_ipld_hamt::{BytesKey, Change, Config, Error, Hamt, Hash, HashAlgorithm, Sha256};
 use multihash::Code;
 use quickcheck::Arbitrary;
 use rand::seq::SliceRandom;
@@ -386,6 +386,296 @@ fn for_each(factory: HamtFactory, stats: Option<BSStats>, mut cids: CidChecker)
     }
 }
 
+fn diff(factory: HamtFactory) {
+    let mem = MemoryBlockstore::default();
+    let store = TrackingBlockstore::new(&mem);
+
+    let mut hamt: Hamt<_, BytesKey> = factory.new_with_bit_width(&store, 5);
+    for i in 0..200 {
+        hamt.set(tstring(i), tstring(i)).unwrap();
+    }
+    let before_cid = hamt.flush().unwrap();
+
+    hamt.set(tstring(1), tstring("modified")).unwrap();
+    hamt.delete(&tstring(2)).unwrap();
+    hamt.set(tstring(200), tstring(200)).unwrap();
+
+    let mut expected = vec![
+        Change::Modified {
+            key: tstring(1),
+            before: tstring(1),
+            after: tstring("modified"),
+        },
+        Change::Removed {
+            key: tstring(2),
+            value: tstring(2),
+        },
+        Change::Added {
+            key: tstring(200),
+            value: tstring(200),
+        },
+    ];
+    expected.sort_by_key(|c| c.key().0.clone());
+
+    let sorted = |mut changes: Vec<Change<BytesKey, BytesKey>>| {
+        changes.sort_by_key(|c| c.key().0.clone());
+        changes
+    };
+
+    // Diff against a dirty HAMT.
+    let before: Hamt<_, BytesKey> = factory.load_with_bit_width(&before_cid, &store, 5).unwrap();
+    assert_eq!(sorted(before.diff(&hamt).unwrap()), expected);
+
+    // Diff two flushed HAMTs, skipping unchanged sub-trees.
+    let after_cid = hamt.flush().unwrap();
+    let reads = store.stats.borrow().r;
+    let before: Hamt<_, BytesKey> = factory.load_with_bit_width(&before_cid, &store, 5).unwrap();
+    let after: Hamt<_, BytesKey> = factory.load_with_bit_width(&after_cid, &store, 5).unwrap();
+    assert_eq!(sorted(before.diff(&after).unwrap()), expected);
+    // Each HAMT has ~30 nodes, but we only need to load the roots and the paths to the changes.
+    assert!(store.stats.borrow().r - reads < 15);
+
+    // And in reverse.
+    let reversed: Vec<_> = expected
+        .into_iter()
+        .map(|c| match c {
+            Change::Added { key, value } => Change::Removed { key, value },
+            Change::Removed { key, value } => Change::Added { key, value },
+            Change::Modified { key, before, after } => Change::Modified {
+                key,
+                before: after,
+                after: before,
+            },
+        })
+        .collect();
+    assert_eq!(sorted(after.diff(&before).unwrap()), reversed);
+
+    assert_eq!(after.diff(&after).unwrap(), vec![]);
+}
+
+fn iter(factory: HamtFactory) {
+    let store = MemoryBlockstore::default();
+
+    let mut hamt: Hamt<_, BytesKey> = factory.new_with_bit_width(&store, 5);
+    for i in 0..200 {
+        hamt.set(tstring(i), tstring(i)).unwrap();
+    }
+
+    // Entries are returned in hash order, whether or not the HAMT is flushed.
+    let all: Vec<_> = hamt
+        .iter()
+        .map(|r| r.map(|(k, _)| k.clone()))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(all.len(), 200);
+    assert!(all
+        .windows(2)
+        .all(|w| Sha256::hash(&w[0]) < Sha256::hash(&w[1])));
+
+    let c = hamt.flush().unwrap();
+    let hamt: Hamt<_, BytesKey> = factory.load_with_bit_width(&c, &store, 5).unwrap();
+    let reloaded: Vec<_> = hamt
+        .iter()
+        .map(|r| r.map(|(k, _)| k.clone()))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(reloaded, all);
+
+    // Page through the HAMT, reloading it for every page.
+    let mut paged = Vec::new();
+    let mut cursor = None;
+    loop {
+        let hamt: Hamt<_, BytesKey> = factory.load_with_bit_width(&c, &store, 5).unwrap();
+        let mut iter = match &cursor {
+            Some(cursor) => hamt.iter_from(cursor).unwrap(),
+            None => hamt.iter(),
+        };
+        let page: Vec<_> = iter
+            .by_ref()
+            .take(7)
+            .map(|r| r.map(|(k, _)| k.clone()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        paged.extend_from_slice(&page);
+        if page.len() < 7 {
+            break;
+        }
+        cursor = iter.cursor();
+    }
+    assert_eq!(paged, all);
+
+    // Cursors remain valid when the HAMT is modified, even if the last key is deleted.
+    let mut hamt: Hamt<_, BytesKey> = factory.load_with_bit_width(&c, &store, 5).unwrap();
+    let mut iter = hamt.iter();
+    for r in iter.by_ref().take(100) {
+        r.unwrap();
+    }
+    let cursor = iter.cursor().unwrap();
+    hamt.delete(&all[99]).unwrap();
+    hamt.delete(&all[100]).unwrap();
+    let rest: Vec<_> = hamt
+        .iter_from(&cursor)
+        .unwrap()
+        .map(|r| r.map(|(k, _)| k.clone()))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(rest, &all[101..]);
+}
+
+/// Test that paging through a HAMT is equivalent to iterating over it in one go.
+fn prop_iter_paged<const N: u32>(factory: HamtFactory, ops: LimitedKeyOps<N>, page: u8) -> bool {
+    let store = MemoryBlockstore::default();
+    let page = page as usize % 10 + 1;
+
+    let mut hamt = factory.new(&store);
+    for op in ops {
+        match op {
+            Operation::Set((k, v)) => {
+                hamt.set(k.0, v).unwrap();
+            }
+            Operation::Delete(k) => {
+                hamt.delete(&k.0).unwrap();
+            }
+        }
+    }
+
+    let mut all = Vec::new();
+    hamt.for_each(|k, v| {
+        all.push((*k, *v));
+        Ok(())
+    })
+    .unwrap();
+
+    let mut paged = Vec::new();
+    let mut cursor = None;
+    loop {
+        let mut iter = match &cursor {
+            Some(cursor) => hamt.iter_from(cursor).unwrap(),
+            None => hamt.iter(),
+        };
+        let len = paged.len();
+        for r in iter.by_ref().take(page) {
+            let (k, v) = r.unwrap();
+            paged.push((*k, *v));
+        }
+        if paged.len() - len < page {
+            break;
+        }
+        cursor = iter.cursor();
+    }
+
+    all.sort_unstable();
+    paged.sort_unstable();
+    all == paged
+}
+
+fn proof(factory: HamtFactory) {
+    let store = MemoryBlockstore::default();
+    let conf = Config {
+        bit_width: 5,
+        ..factory.conf
+    };
+
+    let mut hamt: Hamt<_, BytesKey> = factory.new_with_bit_width(&store, 5);
+    for i in 0..200 {
+        hamt.set(tstring(i), tstring(i)).unwrap();
+    }
+    // Proofs can only be generated from flushed state.
+    assert!(hamt.prove(&tstring(1)).is_err());
+    let root = hamt.flush().unwrap();
+
+    let verify = |proof: &Proof, k| {
+        Hamt::<_, BytesKey>::verify_proof(proof, &root, &tstring(k), conf.clone())
+    };
+
+    #[cfg(not(feature = "ignore-dead-links"))]
+    let mut misses = 0;
+    for i in 0..300 {
+        let proof = hamt.prove(&tstring(i)).unwrap();
+        let expected = (i < 200).then(|| tstring(i));
+        assert_eq!(verify(&proof, i).unwrap(), expected);
+        // Only the blocks on the path to the key are included.
+        assert!(proof.blocks().len() <= 3 + conf.min_data_depth as usize);
+
+        // A proof can't be used to prove anything off its path. (With dead links ignored, blocks
+        // missing from the proof are skipped instead.)
+        #[cfg(not(feature = "ignore-dead-links"))]
+        match verify(&proof, i + 1) {
+            Ok(v) => assert_eq!(v, (i + 1 < 200).then(|| tstring(i + 1))),
+            Err(_) => misses += 1,
+        }
+    }
+    #[cfg(not(feature = "ignore-dead-links"))]
+    assert!(misses > 0);
+
+    // Proofs are checked against the root and the blocks against their CIDs.
+    let proof = hamt.prove(&tstring(1)).unwrap();
+    let other_root = factory
+        .new::<_, BytesKey, BytesKey>(&store)
+        .flush()
+        .unwrap();
+    assert!(
+        Hamt::<_, BytesKey>::verify_proof(&proof, &other_root, &tstring(1), conf.clone()).is_err()
+    );
+    let mut blocks = proof.into_blocks();
+    let (_, last) = blocks.last_mut().unwrap();
+    *last = fvm_ipld_encoding::to_vec(&tstring("forged")).unwrap();
+    assert!(verify(&Proof::new(root, blocks), 1).is_err());
+}
+
+/// Test that diffing two HAMTs is equivalent to diffing their contents.
+fn prop_diff<const N: u32>(
+    factory: HamtFactory,
+    before: LimitedKeyOps<N>,
+    after: LimitedKeyOps<N>,
+) -> bool {
+    let store = MemoryBlockstore::default();
+
+    let apply = |ops: LimitedKeyOps<N>| {
+        let mut map = HashMap::new();
+        let mut hamt = factory.new(&store);
+        for op in ops {
+            match op {
+                Operation::Set((k, v)) => {
+                    map.insert(k.0, v);
+                    hamt.set(k.0, v).unwrap();
+                }
+                Operation::Delete(k) => {
+                    map.remove(&k.0);
+                    hamt.delete(&k.0).unwrap();
+                }
+            }
+        }
+        hamt.flush().unwrap();
+        (map, hamt)
+    };
+    let (before_map, before) = apply(before);
+    let (after_map, after) = apply(after);
+
+    let mut expected = Vec::new();
+    for (&key, &value) in &before_map {
+        match after_map.get(&key) {
+            None => expected.push(Change::Removed { key, value }),
+            Some(&after) if after != value => expected.push(Change::Modified {
+                key,
+                before: value,
+                after,
+            }),
+            Some(_) => {}
+        }
+    }
+    for (&key, &value) in &after_map {
+        if !before_map.contains_key(&key) {
+            expected.push(Change::Added { key, value });
+        }
+    }
+    expected.sort_by_key(|c| *c.key());
+
+    let mut changes = before.diff(&after).unwrap();
+    changes.sort_by_key(|c| *c.key());
+    changes == expected
+}
+
 #[cfg(feature = "identity")]
 fn add_and_remove_keys(
     bit_width: u32,
@@ -446,7 +736,7 @@ fn canonical_structure() {
         &[b"K"],
         &[b"B"],
         "bafy2bzacecosy45hp4sz2t4o4flxvntnwjy7yaq43bykci22xycpeuj542lse",
-        BSStats {r: 2, w: 2, br: 38, bw: 38},
+        BSStats {r: 2, w: 2, br: 38, bw: 38, ..Default::default()},
     );
 
     #[rustfmt::skip]
@@ -455,7 +745,7 @@ fn canonical_structure() {
         &[b"K0", b"K1", b"KAA1", b"KAA2", b"KAA3"],
         &[b"KAA4"],
         "bafy2bzaceaqdaj5aqkwugr7wx4to3fahynoqlxuo5j6xznly3khazgyxihkbo",
-        BSStats {r:3, w:4, br:163, bw:214},
+        BSStats {r:3, w:4, br:163, bw:214, ..Default::default()},
     );
 }
 
@@ -476,16 +766,16 @@ fn canonical_structure_alt_bit_width() {
 
     #[rustfmt::skip]
     let kb_stats = [
-        BSStats {r: 2, w: 2, br: 22, bw: 22},
-        BSStats {r: 2, w: 2, br: 24, bw: 24},
-        BSStats {r: 2, w: 2, br: 28, bw: 28},
+        BSStats {r: 2, w: 2, br: 22, bw: 22, ..Default::default()},
+        BSStats {r: 2, w: 2, br: 24, bw: 24, ..Default::default()},
+        BSStats {r: 2, w: 2, br: 28, bw: 28, ..Default::default()},
     ];
 
     #[rustfmt::skip]
     let other_stats = [
-        BSStats {r: 3, w: 4, br: 139, bw: 182},
-        BSStats {r: 3, w: 4, br: 146, bw: 194},
-        BSStats {r: 3, w: 4, br: 154, bw: 206},
+        BSStats {r: 3, w: 4, br: 139, bw: 182, ..Default::default()},
+        BSStats {r: 3, w: 4, br: 146, bw: 194, ..Default::default()},
+        BSStats {r: 3, w: 4, br: 154, bw: 206, ..Default::default()},
     ];
 
     for i in 5..8 {
@@ -749,7 +1039,7 @@ mod test_default {
     #[test]
     fn test_set_if_absent() {
         #[rustfmt::skip]
-        let stats = BSStats {r: 1, w: 1, br: 63, bw: 63};
+        let stats = BSStats {r: 1, w: 1, br: 63, bw: 63, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzaced2tgnlsq4n2ioe6ldy75fw3vlrrkyfv4bq6didbwoob2552zvpuk",
         ]);
@@ -759,7 +1049,7 @@ mod test_default {
     #[test]
     fn set_with_no_effect_does_not_put() {
         #[rustfmt::skip]
-        let stats = BSStats {r:0, w:18, br:0, bw:1282};
+        let stats = BSStats {r:0, w:18, br:0, bw:1282, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzacebjilcrsqa4uyxuh36gllup4rlgnvwgeywdm5yqq2ks4jrsj756qq",
             "bafy2bzacea7biyabzk7v7le2rrlec5tesjbdnymh5sk4lfprxibg4rtudwtku",
@@ -771,7 +1061,7 @@ mod test_default {
     #[test]
     fn delete() {
         #[rustfmt::skip]
-        let stats = BSStats {r:1, w:2, br:79, bw:139};
+        let stats = BSStats {r:1, w:2, br:79, bw:139, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzacebql36crv4odvxzstx2ubaczmawy2tlljxezvorcsoqeyyojxkrom",
             "bafy2bzaced7up7wkm7cirieh5bs4iyula5inrprihmjzozmku3ywvekzzmlyi",
@@ -782,7 +1072,7 @@ mod test_default {
     #[test]
     fn delete_case() {
         #[rustfmt::skip]
-        let stats = BSStats {r: 1, w: 2, br: 31, bw: 34};
+        let stats = BSStats {r: 1, w: 2, br: 31, bw: 34, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzaceb2hikcc6tfuuuuehjstbiq356oruwx6ejyse77zupq445unranv6",
             "bafy2bzaceamp42wmmgr2g2ymg46euououzfyck7szknvfacqscohrvaikwfay",
@@ -793,7 +1083,7 @@ mod test_default {
     #[test]
     fn reload_empty() {
         #[rustfmt::skip]
-        let stats = BSStats {r: 1, w: 2, br: 3, bw: 6};
+        let stats = BSStats {r: 1, w: 2, br: 3, bw: 6, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzaceamp42wmmgr2g2ymg46euououzfyck7szknvfacqscohrvaikwfay",
         ]);
@@ -803,7 +1093,7 @@ mod test_default {
     #[test]
     fn set_delete_many() {
         #[rustfmt::skip]
-        let stats = BSStats {r: 0, w: 93, br: 0, bw: 11734};
+        let stats = BSStats {r: 0, w: 93, br: 0, bw: 11734, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzaceczhz54xmmz3xqnbmvxfbaty3qprr6dq7xh5vzwqbirlsnbd36z7a",
             "bafy2bzacecxcp736xkl2mcyjlors3tug6vdlbispbzxvb75xlrhthiw2xwxvw",
@@ -815,7 +1105,7 @@ mod test_default {
     #[test]
     fn for_each() {
         #[rustfmt::skip]
-        let stats = BSStats {r: 30, w: 30, br: 3209, bw: 3209};
+        let stats = BSStats {r: 30, w: 30, br: 3209, bw: 3209, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzaceczhz54xmmz3xqnbmvxfbaty3qprr6dq7xh5vzwqbirlsnbd36z7a",
             "bafy2bzaceczhz54xmmz3xqnbmvxfbaty3qprr6dq7xh5vzwqbirlsnbd36z7a",
@@ -823,10 +1113,25 @@ mod test_default {
         super::for_each(HamtFactory::default(), Some(stats), cids);
     }
 
+    #[test]
+    fn diff() {
+        super::diff(HamtFactory::default())
+    }
+
+    #[test]
+    fn iter() {
+        super::iter(HamtFactory::default())
+    }
+
+    #[test]
+    fn proof() {
+        super::proof(HamtFactory::default())
+    }
+
     #[test]
     fn clean_child_ordering() {
         #[rustfmt::skip]
-        let stats = BSStats {r: 3, w: 11, br: 1449, bw: 1751};
+        let stats = BSStats {r: 3, w: 11, br: 1449, bw: 1751, ..Default::default()};
         let cids = CidChecker::new(vec![
             "bafy2bzacebqox3gtng4ytexyacr6zmaliyins3llnhbnfbcrqmhzuhmuuawqk",
             "bafy2bzacedlyeuub3mo4aweqs7zyxrbldsq2u4a2taswubudgupglu2j4eru6",
@@ -843,6 +1148,16 @@ mod test_default {
     fn prop_cid_ops_reduced(ops: LimitedKeyOps<10>) -> bool {
         super::prop_cid_ops_reduced(HamtFactory::default(), ops)
     }
+
+    #[quickcheck]
+    fn prop_diff(before: LimitedKeyOps<100>, after: LimitedKeyOps<100>) -> bool {
+        super::prop_diff(HamtFactory::default(), before, after)
+    }
+
+    #[quickcheck]
+    fn prop_iter_paged(ops: LimitedKeyOps<100>, page: u8) -> bool {
+        super::prop_iter_paged(HamtFactory::default(), ops, page)
+    }
 }
 
 /// Run all the tests with a different configuration.
@@ -913,6 +1228,21 @@ macro_rules! test_hamt_mod {
                 super::for_each($factory, None, CidChecker::empty())
             }
 
+            #[test]
+            fn diff() {
+                super::diff($factory)
+            }
+
+            #[test]
+            fn iter() {
+                super::iter($factory)
+            }
+
+            #[test]
+            fn proof() {
+                super::proof($factory)
+            }
+
             #[test]
             fn clean_child_ordering() {
                 super::clean_child_ordering($factory, None, CidChecker::empty())
@@ -930,6 +1260,16 @@ macro_rules! test_hamt_mod {
             fn prop_cid_ops_reduced(ops: LimitedKeyOps<10>) -> bool {
                 super::prop_cid_ops_reduced($factory, ops)
             }
+
+            #[quickcheck]
+            fn prop_diff(before: LimitedKeyOps<100>, after: LimitedKeyOps<100>) -> bool {
+                super::prop_diff($factory, before, after)
+            }
+
+            #[quickcheck]
+            fn prop_iter_paged(ops: LimitedKeyOps<100>, page: u8) -> bool {
+                super::prop_iter_paged($factory, ops, page)
+            }
         }
     };
 }
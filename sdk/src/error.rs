@@ -12,6 +12,8 @@ pub enum StateUpdateError {
     ActorDeleted,
     #[error("current execution context is read-only")]
     ReadOnly,
+    #[error("new state root is not reachable")]
+    NotReachable,
 }
 
 #[derive(Copy, Clone, Debug, Error, Eq, PartialEq)]
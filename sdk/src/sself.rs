@@ -38,6 +38,7 @@ pub fn set_root(cid: &Cid) -> Result<(), StateUpdateError> {
         sys::sself::set_root(buf.as_ptr()).map_err(|e| match e {
             ErrorNumber::IllegalOperation => StateUpdateError::ActorDeleted,
             ErrorNumber::ReadOnly => StateUpdateError::ReadOnly,
+            ErrorNumber::NotFound => StateUpdateError::NotReachable,
             e => panic!("unexpected error from `self::set_root` syscall: {}", e),
         })
     }
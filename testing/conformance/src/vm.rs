@@ -14,6 +14,7 @@ use fvm::kernel::*;
 use fvm::machine::limiter::MemoryLimiter;
 use fvm::machine::{DefaultMachine, Machine, MachineContext, Manifest, NetworkConfig};
 use fvm::state_tree::{ActorState, StateTree};
+use fvm::trace::ActorLog;
 use fvm::DefaultKernel;
 use fvm_ipld_blockstore::MemoryBlockstore;
 use fvm_ipld_car::load_car_unchecked;
@@ -44,7 +45,7 @@ const DEFAULT_BASE_FEE: u64 = 100;
 #[derive(Clone)]
 pub struct TestData {
     circ_supply: TokenAmount,
-    price_list: PriceList,
+    price_list: Arc<PriceList>,
 }
 
 /// Statistics about the resources used by test vector executions.
@@ -365,6 +366,10 @@ where
     fn append_event(&mut self, evt: StampedEvent) {
         self.0.append_event(evt)
     }
+
+    fn append_log(&mut self, log: ActorLog) {
+        self.0.append_log(log)
+    }
 }
 
 /// A kernel for intercepting syscalls.
@@ -602,7 +607,7 @@ where
     C: CallManager<Machine = TestMachine<M>>,
     K: Kernel<CallManager = TestCallManager<C>>,
 {
-    fn log(&self, msg: String) {
+    fn log(&mut self, msg: String) {
         self.0.log(msg)
     }
 
@@ -1,7 +1,10 @@
 // Copyright 2021-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
+use std::sync::Arc;
+
 use anyhow::{anyhow, Context, Result};
 use cid::Cid;
+use fvm::artifact::{Artifact, MemoryArtifactSink};
 use fvm::call_manager::DefaultCallManager;
 use fvm::engine::EnginePool;
 use fvm::executor::DefaultExecutor;
@@ -51,6 +54,8 @@ pub struct Tester<B: Blockstore + 'static, E: Externs + 'static> {
     pub executor: Option<IntegrationExecutor<B, E>>,
     // State tree constructed before instantiating the Machine
     pub state_tree: Option<StateTree<B>>,
+    // Debug artifacts stored by actors and not yet taken
+    artifacts: Arc<MemoryArtifactSink>,
 }
 
 impl<B, E> Tester<B, E>
@@ -92,6 +97,7 @@ where
             state_tree: Some(state_tree),
             accounts_code_cid,
             placeholder_code_cid,
+            artifacts: Default::default(),
         })
     }
 
@@ -257,7 +263,9 @@ where
 
         let mut mc = nc.for_epoch(0, 0, state_root);
         mc.set_base_fee(TokenAmount::from_atto(DEFAULT_BASE_FEE))
-            .enable_tracing();
+            .enable_tracing()
+            .enable_actor_log_collection()
+            .set_artifact_sink(self.artifacts.clone());
 
         // Custom configuration.
         configure_mc(&mut mc);
@@ -277,6 +285,12 @@ where
         Ok(())
     }
 
+    /// Takes the debug artifacts stored by actors since they were last taken. Taking them after each
+    /// message returns the artifacts that message produced.
+    pub fn take_artifacts(&self) -> Vec<Artifact> {
+        self.artifacts.take()
+    }
+
     /// Get blockstore
     pub fn blockstore(&self) -> &dyn Blockstore {
         if self.executor.is_some() {
@@ -122,6 +122,21 @@ fn events_test() {
     assert_eq!(ExitCode::SYS_OUT_OF_GAS, res.msg_receipt.exit_code);
     assert!(res.msg_receipt.events_root.is_none());
     assert_eq!(0, res.events.len());
+
+    // === Events exceeding the limits are rejected ===
+    let message = Message {
+        method_num: 6,
+        sequence: 5,
+        gas_limit: 1000000000,
+        ..message
+    };
+    let res = executor
+        .execute_message(message, ApplyKind::Explicit, 100)
+        .unwrap();
+
+    assert_eq!(ExitCode::OK, res.msg_receipt.exit_code);
+    assert!(res.msg_receipt.events_root.is_none());
+    assert_eq!(0, res.events.len());
 }
 
 fn setup() -> (
@@ -152,8 +167,16 @@ fn setup() -> (
         .set_actor_from_bin(wasm_bin, state_cid, actor, TokenAmount::zero())
         .unwrap();
 
-    // Instantiate machine
-    tester.instantiate_machine(DummyExterns).unwrap();
+    // Instantiate machine, validating events (the actor emits events exceeding the limits).
+    tester
+        .instantiate_machine_with_config(
+            DummyExterns,
+            |nc| {
+                nc.validate_events();
+            },
+            |_| (),
+        )
+        .unwrap();
 
     let executor = tester.executor.unwrap();
     (executor, sender, actor)
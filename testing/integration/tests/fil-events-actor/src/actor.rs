@@ -1,10 +1,11 @@
 // Copyright 2021-2023 Protocol Labs
 // SPDX-License-Identifier: Apache-2.0, MIT
 use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::BytesSer;
 use fvm_sdk as sdk;
 use fvm_shared::address::Address;
 use fvm_shared::bigint::Zero;
-use fvm_shared::error::ExitCode;
+use fvm_shared::error::{ErrorNumber, ExitCode};
 use fvm_shared::event::{Entry, Flags};
 use serde_tuple::*;
 
@@ -28,6 +29,7 @@ pub fn invoke(params: u32) -> u32 {
     const EMIT_MALFORMED: u64 = 3;
     const EMIT_SUBCALLS: u64 = 4;
     const EMIT_SUBCALLS_REVERT: u64 = 5;
+    const EMIT_INVALID: u64 = 6;
 
     // Emit a single-entry event.
     let payload = EventPayload1 {
@@ -143,6 +145,41 @@ pub fn invoke(params: u32) -> u32 {
                 sdk::vm::abort(ExitCode::USR_ASSERTION_FAILED.value(), None);
             }
         }
+        EMIT_INVALID => {
+            let entry = |flags, key: &str, value: Vec<u8>| Entry {
+                flags,
+                key: key.to_owned(),
+                value: value.into(),
+            };
+            let valid_value = fvm_ipld_encoding::to_vec(&payload).unwrap();
+            let invalid = [
+                // Too many entries.
+                vec![entry(Flags::empty(), "foo", valid_value.clone()); 256],
+                // A key that's too long.
+                vec![entry(Flags::empty(), &"a".repeat(32), valid_value.clone())],
+                // Unknown flags.
+                vec![entry(
+                    unsafe { Flags::from_bits_unchecked(0x80) },
+                    "foo",
+                    valid_value,
+                )],
+                // A value that's too large.
+                vec![entry(
+                    Flags::empty(),
+                    "foo",
+                    fvm_ipld_encoding::to_vec(&BytesSer(&[0u8; 8 << 10])).unwrap(),
+                )],
+                // A value that isn't DAG-CBOR.
+                vec![entry(Flags::empty(), "foo", vec![0xc1, 0x01])],
+            ];
+            for evt in invalid {
+                assert_eq!(
+                    sdk::event::emit_event(&evt.into()),
+                    Err(ErrorNumber::IllegalArgument),
+                    "expected the event to be rejected"
+                );
+            }
+        }
         _ => panic!("invalid method number"),
     }
     0
@@ -9,6 +9,9 @@ include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 pub fn invoke(_: u32) -> u32 {
     use fvm_sdk as sdk;
 
+    sdk::debug::log("hello world".into());
+    sdk::debug::store_artifact("hello.txt", "hello world");
+
     // Conduct method dispatch. Handle input parameters and return data.
     sdk::vm::abort(
         fvm_shared::error::ExitCode::FIRST_USER_EXIT_CODE,
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 use fvm_ipld_encoding::{to_vec, BytesSer, DAG_CBOR};
 use fvm_sdk as sdk;
-use fvm_shared::error::ExitCode;
+use fvm_shared::error::{ErrorNumber, ExitCode};
 
 #[no_mangle]
 pub fn invoke(_: u32) -> u32 {
@@ -14,6 +14,11 @@ pub fn invoke(_: u32) -> u32 {
     }));
 
     test_read_block();
+    // Only checked when asked to, as other tests run this actor without enforcing reachability.
+    if sdk::message::method_number() == 3 {
+        test_unreachable_block();
+        test_unreachable_link();
+    }
 
     #[cfg(coverage)]
     sdk::debug::store_artifact("ipld_actor.profraw", minicov::capture_coverage());
@@ -130,3 +135,42 @@ fn test_read_block() {
             .expect_err("expected it to fail");
     }
 }
+
+fn test_unreachable_block() {
+    // Flip a bit in the hash of a linked block to get a CID this actor can't reach.
+    let k = sdk::ipld::put(0xb220, 32, DAG_CBOR, &to_vec(&"reachable").unwrap()).unwrap();
+    sdk::ipld::get(&k).unwrap();
+    let mut k_bytes = k.to_bytes();
+    *k_bytes.last_mut().unwrap() ^= 1;
+
+    unsafe {
+        assert_eq!(
+            sdk::sys::ipld::block_open(k_bytes.as_ptr()).map(|_| ()),
+            Err(ErrorNumber::NotFound),
+            "unreachable blocks can't be opened"
+        );
+        assert_eq!(
+            sdk::sys::sself::set_root(k_bytes.as_ptr()),
+            Err(ErrorNumber::NotFound),
+            "unreachable blocks can't be set as the state root"
+        );
+    }
+}
+
+fn test_unreachable_link() {
+    // A new block may link to reachable blocks...
+    let k = sdk::ipld::put(0xb220, 32, DAG_CBOR, &to_vec(&"linked").unwrap()).unwrap();
+    let mut block = to_vec(&k).unwrap();
+    let linking = sdk::ipld::put(0xb220, 32, DAG_CBOR, &block).unwrap();
+    assert_eq!(sdk::ipld::get(&linking).unwrap(), block);
+    sdk::ipld::get(&k).unwrap();
+
+    // ...but not to unreachable ones. Otherwise, the actor could reach any block by opening a new
+    // block that links to it. Flipping a bit in the encoded link's hash makes it unreachable.
+    *block.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        sdk::ipld::put(0xb220, 32, DAG_CBOR, &block),
+        Err(ErrorNumber::NotFound),
+        "new blocks can't link to unreachable blocks"
+    );
+}
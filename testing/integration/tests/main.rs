@@ -3,6 +3,8 @@
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use anyhow::anyhow;
 use cid::Cid;
@@ -12,7 +14,11 @@ use fil_hello_world_actor::WASM_BINARY as HELLO_BINARY;
 use fil_ipld_actor::WASM_BINARY as IPLD_BINARY;
 use fil_stack_overflow_actor::WASM_BINARY as OVERFLOW_BINARY;
 use fil_syscall_actor::WASM_BINARY as SYSCALL_BINARY;
-use fvm::executor::{ApplyKind, Executor, ThreadedExecutor};
+use fvm::artifact::ArtifactSource;
+use fvm::call_manager::{ExecutionObserver, InvocationResult};
+use fvm::executor::{ApplyKind, EstimateOptions, Executor, ThreadedExecutor};
+use fvm::gas::GasCharge;
+use fvm::trace::{ActorLog, CallTrace, CallTree, ExecutionEvent};
 use fvm_integration_tests::dummy::DummyExterns;
 use fvm_integration_tests::tester::{Account, IntegrationExecutor};
 use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
@@ -29,7 +35,7 @@ use num_traits::Zero;
 mod bundles;
 use bundles::*;
 use fvm_shared::chainid::ChainID;
-use fvm_shared::ActorID;
+use fvm_shared::{ActorID, MethodNum};
 
 /// The state object.
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, Default)]
@@ -78,11 +84,42 @@ fn hello_world() {
 
     let res = tester
         .executor
+        .as_mut()
         .unwrap()
         .execute_message(message, ApplyKind::Explicit, 100)
         .unwrap();
 
-    assert_eq!(res.msg_receipt.exit_code.value(), 16)
+    assert_eq!(res.msg_receipt.exit_code.value(), 16);
+
+    // Logs are kept even though the actor aborted.
+    assert_eq!(
+        res.logs,
+        vec![ActorLog {
+            actor: 10000,
+            method: 1,
+            invocation: 1,
+            nonce: 0,
+            msg: "hello world".into(),
+        }]
+    );
+
+    // So are artifacts.
+    let artifacts = tester.take_artifacts();
+    assert_eq!(artifacts.len(), 1);
+    let ArtifactSource {
+        origin,
+        nonce,
+        actor,
+        invocation,
+        ..
+    } = artifacts[0].source;
+    assert_eq!(
+        (origin, nonce, actor, invocation),
+        (sender[0].0, 0, 10000, 1)
+    );
+    assert_eq!(artifacts[0].name, "hello.txt");
+    assert_eq!(artifacts[0].data, b"hello world");
+    assert!(tester.take_artifacts().is_empty());
 }
 
 #[test]
@@ -110,15 +147,23 @@ fn ipld() {
         .set_actor_from_bin(wasm_bin, state_cid, actor_address, TokenAmount::zero())
         .unwrap();
 
-    // Instantiate machine
-    tester.instantiate_machine(DummyExterns).unwrap();
+    // Instantiate machine, checking reachability (the actor tests it).
+    tester
+        .instantiate_machine_with_config(
+            DummyExterns,
+            |nc| {
+                nc.enforce_reachability();
+            },
+            |_| (),
+        )
+        .unwrap();
 
-    // Send message
+    // Send message (method 3 also tests reachability)
     let message = Message {
         from: sender[0].1,
         to: actor_address,
         gas_limit: 1000000000,
-        method_num: 1,
+        method_num: 3,
         ..Message::default()
     };
 
@@ -135,6 +180,210 @@ fn ipld() {
             panic!("non-zero exit code {}", res.msg_receipt.exit_code)
         }
     }
+
+    // The call tree records the blocks written and read back by the actor.
+    let [call]: [CallTrace; 1] = res.call_tree.calls.try_into().unwrap();
+    assert_eq!(call.to, actor_address);
+    assert_eq!(call.exit_code(), Some(ExitCode::OK));
+    assert!(call.subcalls.is_empty());
+    assert!(!call.ipld_writes.is_empty());
+    assert!(call.ipld_writes.iter().all(|k| call.ipld_reads.contains(k)));
+
+    // The flat trace has the same shape.
+    let tree = CallTree::from_flat(&res.exec_trace).unwrap();
+    assert_eq!(tree.calls.len(), 1);
+    assert_eq!(tree.calls[0].gas_charges.len(), call.gas_charges.len());
+    assert_eq!(tree.calls[0].ipld_reads, call.ipld_reads);
+    assert_eq!(tree.calls[0].ipld_writes, call.ipld_writes);
+
+    // Every block access is attributed to the actor.
+    assert!(res
+        .exec_trace
+        .iter()
+        .any(|evt| matches!(evt, ExecutionEvent::BlockLink { actor: 10000, .. })));
+}
+
+#[derive(Default)]
+struct CountingObserver {
+    calls: Mutex<Vec<(ActorID, Address)>>,
+    returns: AtomicUsize,
+    gas_charges: AtomicUsize,
+    syscalls: Mutex<HashSet<(&'static str, &'static str)>>,
+}
+
+impl ExecutionObserver for CountingObserver {
+    fn on_call_start(&self, from: ActorID, to: &Address, _method: MethodNum, _value: &TokenAmount) {
+        self.calls.lock().unwrap().push((from, *to));
+    }
+
+    fn on_call_end(&self, _result: &fvm::kernel::Result<InvocationResult>) {
+        self.returns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_gas_charge(&self, _charge: &GasCharge) {
+        self.gas_charges.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_syscall(&self, module: &'static str, name: &'static str) {
+        self.syscalls.lock().unwrap().insert((module, name));
+    }
+}
+
+#[test]
+fn observer() {
+    // Instantiate tester
+    let mut tester = new_tester(
+        NetworkVersion::V18,
+        StateTreeVersion::V5,
+        MemoryBlockstore::default(),
+    )
+    .unwrap();
+
+    let [(sender_id, sender)]: [Account; 1] = tester.create_accounts().unwrap();
+
+    let wasm_bin = IPLD_BINARY.unwrap();
+
+    // Set actor state
+    let actor_state = State::default();
+    let state_cid = tester.set_state(&actor_state).unwrap();
+
+    // Set actor
+    let actor_address = Address::new_id(10000);
+
+    tester
+        .set_actor_from_bin(wasm_bin, state_cid, actor_address, TokenAmount::zero())
+        .unwrap();
+
+    // Instantiate machine with the observer installed.
+    let observer = Arc::new(CountingObserver::default());
+    tester
+        .instantiate_machine_with_config(
+            DummyExterns,
+            |_| (),
+            |mc| {
+                mc.set_observer(observer.clone());
+            },
+        )
+        .unwrap();
+
+    // Send message
+    let message = Message {
+        from: sender,
+        to: actor_address,
+        gas_limit: 1000000000,
+        method_num: 1,
+        ..Message::default()
+    };
+
+    let res = tester
+        .executor
+        .unwrap()
+        .execute_message(message, ApplyKind::Explicit, 100)
+        .unwrap();
+    assert!(res.msg_receipt.exit_code.is_success());
+
+    assert_eq!(
+        *observer.calls.lock().unwrap(),
+        [(sender_id, actor_address)]
+    );
+    assert_eq!(observer.returns.load(Ordering::Relaxed), 1);
+
+    // Every gas charge was observed, including those made outside of the call.
+    let traced_charges = res
+        .exec_trace
+        .iter()
+        .filter(|evt| matches!(evt, ExecutionEvent::GasCharge(_)))
+        .count();
+    assert_eq!(observer.gas_charges.load(Ordering::Relaxed), traced_charges);
+    assert!(observer
+        .syscalls
+        .lock()
+        .unwrap()
+        .contains(&("ipld", "block_open")));
+}
+
+#[test]
+fn estimate_gas() {
+    // Instantiate tester
+    let mut tester = new_tester(
+        NetworkVersion::V18,
+        StateTreeVersion::V5,
+        MemoryBlockstore::default(),
+    )
+    .unwrap();
+
+    let sender: [Account; 1] = tester.create_accounts().unwrap();
+
+    let wasm_bin = IPLD_BINARY.unwrap();
+
+    // Set actor state
+    let actor_state = State::default();
+    let state_cid = tester.set_state(&actor_state).unwrap();
+
+    // Set actor
+    let actor_address = Address::new_id(10000);
+
+    tester
+        .set_actor_from_bin(wasm_bin, state_cid, actor_address, TokenAmount::zero())
+        .unwrap();
+
+    // Instantiate machine
+    tester.instantiate_machine(DummyExterns).unwrap();
+
+    let executor = tester.executor.as_mut().unwrap();
+    let root = executor.flush().unwrap();
+
+    // Estimate with a wrong nonce; this is only allowed when skipping the sequence check.
+    let message = Message {
+        from: sender[0].1,
+        to: actor_address,
+        gas_limit: fvm_shared::BLOCK_GAS_LIMIT,
+        method_num: 1,
+        sequence: 10,
+        ..Message::default()
+    };
+
+    let res = executor
+        .estimate_message(message.clone(), 100, EstimateOptions::default())
+        .unwrap();
+    assert_eq!(
+        res.apply_ret.msg_receipt.exit_code,
+        ExitCode::SYS_SENDER_STATE_INVALID
+    );
+
+    let estimate = executor
+        .estimate_message(
+            message,
+            100,
+            EstimateOptions {
+                skip_sequence_check: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(estimate.apply_ret.msg_receipt.exit_code.is_success());
+    assert!(estimate.gas_used > 0);
+    assert!(estimate.gas_limit >= estimate.gas_used);
+
+    // Estimating must not have touched the state.
+    assert_eq!(executor.flush().unwrap(), root);
+
+    // Now apply the message for real, with the recommended limit.
+    let message = Message {
+        from: sender[0].1,
+        to: actor_address,
+        gas_limit: estimate.gas_limit,
+        method_num: 1,
+        ..Message::default()
+    };
+
+    let res = executor
+        .execute_message(message, ApplyKind::Explicit, 100)
+        .unwrap();
+
+    assert!(res.msg_receipt.exit_code.is_success());
+    assert_eq!(res.msg_receipt.gas_used, estimate.gas_used);
+    assert!(res.over_estimation_burn.is_zero());
 }
 
 #[test]
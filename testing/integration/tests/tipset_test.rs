@@ -0,0 +1,114 @@
+// Copyright 2021-2023 Protocol Labs
+// SPDX-License-Identifier: Apache-2.0, MIT
+mod bundles;
+use bundles::*;
+use cid::Cid;
+use fil_ipld_actor::WASM_BINARY as IPLD_BINARY;
+use fvm::executor::{BlockMessages, ChainMessage, Executor, TipsetExecutor};
+use fvm::machine::{Machine, CRON_ACTOR_ID, REWARD_ACTOR_ID};
+use fvm_integration_tests::dummy::DummyExterns;
+use fvm_integration_tests::tester::Account;
+use fvm_ipld_amt::Amtv0;
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::{to_vec, DAG_CBOR};
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::message::Message;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::state::StateTreeVersion;
+use fvm_shared::version::NetworkVersion;
+use multihash::{Code, MultihashDigest};
+use num_traits::Zero;
+
+fn chain_message(message: Message) -> ChainMessage {
+    let bytes = to_vec(&message).unwrap();
+    ChainMessage {
+        cid: Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&bytes)),
+        raw_length: bytes.len(),
+        message,
+    }
+}
+
+#[test]
+fn apply_tipset() {
+    // Instantiate tester
+    let mut tester = new_tester(
+        NetworkVersion::V18,
+        StateTreeVersion::V5,
+        MemoryBlockstore::default(),
+    )
+    .unwrap();
+
+    let [(_, sender)]: [Account; 1] = tester.create_accounts().unwrap();
+
+    let wasm_bin = IPLD_BINARY.unwrap();
+    let state_cid = tester.set_state(&[(); 0]).unwrap();
+
+    // The IPLD actor succeeds for any method but 3 (which tests reachability), so we also use it to
+    // stand in for the reward and cron actors.
+    let actor_address = Address::new_id(10000);
+    for addr in [
+        actor_address,
+        Address::new_id(REWARD_ACTOR_ID),
+        Address::new_id(CRON_ACTOR_ID),
+    ] {
+        tester
+            .set_actor_from_bin(wasm_bin, state_cid, addr, TokenAmount::zero())
+            .unwrap();
+    }
+
+    // Instantiate machine
+    tester.instantiate_machine(DummyExterns).unwrap();
+
+    let messages: Vec<_> = (0..3)
+        .map(|sequence| {
+            chain_message(Message {
+                from: sender,
+                to: actor_address,
+                gas_limit: 1000000000,
+                method_num: 1,
+                sequence,
+                ..Message::default()
+            })
+        })
+        .collect();
+
+    // The second message is included in both blocks, but must only be applied once.
+    let blocks = vec![
+        BlockMessages {
+            miner: Address::new_id(1000),
+            win_count: 1,
+            messages: vec![messages[0].clone(), messages[1].clone()],
+        },
+        BlockMessages {
+            miner: Address::new_id(1001),
+            win_count: 1,
+            messages: vec![messages[1].clone(), messages[2].clone()],
+        },
+    ];
+
+    let mut executor = TipsetExecutor::new(tester.executor.unwrap());
+    let ret = executor.apply_tipset(blocks).unwrap();
+
+    assert_eq!(ret.message_rets.len(), 3);
+    assert_eq!(ret.events_roots.len(), 3);
+    assert_eq!(ret.reward_rets.len(), 2);
+    for ((cid, apply_ret), msg) in ret.message_rets.iter().zip(&messages) {
+        assert_eq!(cid, &msg.cid);
+        assert!(apply_ret.msg_receipt.exit_code.is_success());
+    }
+
+    // Check the receipts AMT.
+    let receipts: Amtv0<Receipt, _> =
+        Amtv0::load(&ret.receipts_root, executor.blockstore()).unwrap();
+    assert_eq!(receipts.count(), 3);
+    for (i, (_, apply_ret)) in ret.message_rets.iter().enumerate() {
+        assert_eq!(
+            Some(&apply_ret.msg_receipt),
+            receipts.get(i as u64).unwrap()
+        );
+    }
+
+    // The state has already been flushed.
+    assert_eq!(executor.flush().unwrap(), ret.state_root);
+}